@@ -0,0 +1,24 @@
+use minecraft_clone::headless::benchmark::run_benchmark;
+use std::process::exit;
+
+const USAGE: &str = "usage: benchmark [iterations]\n\
+    prints the timings as JSON, run it with --release to compare commits";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let iterations = match &args[..] {
+        [] => 5,
+        [iterations] => match iterations.parse::<usize>() {
+            Ok(iterations) if iterations > 0 => iterations,
+            _ => {
+                eprintln!("invalid iterations {iterations}\n{USAGE}");
+                exit(2);
+            }
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            exit(2);
+        }
+    };
+    print!("{}", run_benchmark(iterations).to_json());
+}
@@ -0,0 +1,90 @@
+use glam::IVec2;
+use minecraft_clone::headless::bitmap::encode_bitmap;
+use minecraft_clone::headless::map::{MapArea, View, render_map};
+use minecraft_clone::headless::parse_seed;
+use std::process::exit;
+use std::time::Instant;
+
+const USAGE: &str = "usage: map_renderer <seed> <output.bmp> [options]
+    --center x,z        block at the center of the map, x,y for cross-sections (default 0,0)
+    --size widthxheight in pixels (default 512x512)
+    --zoom n            every pixel covers 2^n blocks (default 0)
+    --cross-section z   a vertical slice at z instead of the surface seen from above";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|it| it == "--help" || it == "-h") {
+        println!("{USAGE}");
+        return;
+    }
+    match parse_args(&args) {
+        Ok((seed, output, view, area)) => {
+            let start = Instant::now();
+            let image = render_map(seed, view, area);
+            if let Err(e) = std::fs::write(output, encode_bitmap(&image)) {
+                eprintln!("Failed to write {output}: {e}");
+                exit(1);
+            }
+            println!(
+                "Rendered {}x{} pixels in {:.2?}",
+                area.width,
+                area.height,
+                start.elapsed()
+            );
+        }
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            exit(2);
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<(u64, &str, View, MapArea), String> {
+    let [seed, output, options @ ..] = args else {
+        return Err("missing arguments".to_string());
+    };
+    let seed = parse_seed(seed)?;
+    let mut view = View::TopDown;
+    let mut area = MapArea {
+        center: IVec2::ZERO,
+        width: 512,
+        height: 512,
+        zoom: 0,
+    };
+
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options
+            .next()
+            .ok_or_else(|| format!("missing value for {option}"))?;
+        let invalid =
+            |e: &dyn std::fmt::Display| format!("invalid value {value} for {option}: {e}");
+        match option.as_str() {
+            "--center" => {
+                let (x, y) = value.split_once(',').ok_or("expected x,z")?;
+                area.center.x = x.trim().parse().map_err(|e| invalid(&e))?;
+                area.center.y = y.trim().parse().map_err(|e| invalid(&e))?;
+            }
+            "--size" => {
+                let (width, height) = value.split_once('x').ok_or("expected widthxheight")?;
+                area.width = width.parse().map_err(|e| invalid(&e))?;
+                area.height = height.parse().map_err(|e| invalid(&e))?;
+                if area.width == 0 || area.height == 0 || area.width * area.height > 1 << 26 {
+                    return Err(invalid(&"the size must be between 1 and 8192x8192"));
+                }
+            }
+            "--zoom" => {
+                area.zoom = value.parse().map_err(|e| invalid(&e))?;
+                if area.zoom > 16 {
+                    return Err(invalid(&"the largest zoom is 16"));
+                }
+            }
+            "--cross-section" => {
+                let z = value.parse().map_err(|e| invalid(&e))?;
+                view = View::CrossSection { z };
+            }
+            _ => return Err(format!("unknown option {option}")),
+        }
+    }
+    Ok((seed, output, view, area))
+}
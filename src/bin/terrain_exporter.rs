@@ -0,0 +1,39 @@
+use minecraft_clone::headless::export::{Format, TerrainMesh};
+use minecraft_clone::headless::{Region, parse_seed};
+use std::process::exit;
+use std::time::Instant;
+
+const USAGE: &str =
+    "usage: terrain_exporter <seed> <min chunk x,y,z> <max chunk x,y,z> <output.obj|output.glb>";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let [seed, min, max, output] = &args[..] else {
+        eprintln!("{USAGE}");
+        exit(2);
+    };
+    let (seed, region) = match (parse_seed(seed), Region::parse(min, max)) {
+        (Ok(seed), Ok(region)) => (seed, region),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{e}\n{USAGE}");
+            exit(2);
+        }
+    };
+    let Some(format) = Format::from_path(output) else {
+        eprintln!("unknown format of {output}\n{USAGE}");
+        exit(2);
+    };
+
+    let start = Instant::now();
+    let mesh = TerrainMesh::generate(seed, region);
+    println!(
+        "Meshed {} chunks into {} triangles in {:.2?}",
+        region.chunk_count(),
+        mesh.triangle_count(),
+        start.elapsed()
+    );
+    if let Err(e) = mesh.store(output, format) {
+        eprintln!("Failed to write {output}: {e}");
+        exit(1);
+    }
+}
@@ -1,5 +1,5 @@
+use minecraft_clone::headless::bitmap::{Image, Pixel, encode_bitmap};
 use rand::{Rng, RngExt};
-use std::ops::{Index, IndexMut};
 
 const TILE_SIZE: usize = 16;
 
@@ -61,50 +61,21 @@ fn main() {
     tile.fill_random_between(Pixel::rgb(0xf6d7b0), Pixel::rgb(0xe1bf92));
     image.draw_image_at_offset(&mut tile, tile_offset(2, 3));
 
-    std::fs::write("src/renderer/blocks.bmp", encode_bitmap(&image)).unwrap();
-}
+    // lamp
+    tile.fill_random_between(Pixel::rgb(0xffd27f), Pixel::rgb(0xfff3c4));
+    image.draw_image_at_offset(&mut tile, tile_offset(2, 2));
 
-#[derive(Copy, Clone, Default)]
-struct Pixel {
-    r: u8,
-    g: u8,
-    b: u8,
-}
-
-impl Pixel {
-    fn rgb(color: u32) -> Pixel {
-        let c = color.to_le_bytes();
-        Pixel {
-            r: c[2],
-            g: c[1],
-            b: c[0],
-        }
-    }
+    std::fs::write("src/renderer/blocks.bmp", encode_bitmap(&image)).unwrap();
 }
 
-struct Image {
-    width: usize,
-    height: usize,
-    pixels: Vec<Pixel>,
+/// Patterns for the tiles of the atlas
+trait Fill {
+    fn fill_rgb(&mut self);
+    fn fill_random(&mut self);
+    fn fill_random_between(&mut self, a: Pixel, b: Pixel);
 }
 
-impl Image {
-    fn new(width: usize, height: usize) -> Image {
-        Image {
-            width,
-            height,
-            pixels: vec![Default::default(); width * height],
-        }
-    }
-
-    fn draw_image_at_offset(&mut self, other: &Image, (ox, oy): (usize, usize)) {
-        for y in 0..other.height {
-            for x in 0..other.width {
-                self[oy + y][ox + x] = other[y][x];
-            }
-        }
-    }
-
+impl Fill for Image {
     fn fill_rgb(&mut self) {
         self[0][0].r = 255;
         self[0][1].g = 255;
@@ -146,60 +117,3 @@ fn lerp(a: u8, b: u8, t: f32) -> u8 {
     let to = a.max(b);
     from + (t * (to - from) as f32) as u8
 }
-
-impl Index<usize> for Image {
-    type Output = [Pixel];
-
-    fn index(&self, row: usize) -> &Self::Output {
-        &self.pixels[self.width * row..][..self.width]
-    }
-}
-impl IndexMut<usize> for Image {
-    fn index_mut(&mut self, row: usize) -> &mut Self::Output {
-        &mut self.pixels[self.width * row..][..self.width]
-    }
-}
-
-fn encode_bitmap(image: &Image) -> Vec<u8> {
-    let width = i32::try_from(image.width).unwrap();
-    let height = i32::try_from(image.height).unwrap();
-
-    let bf_off_bits: u32 = 138;
-    assert!(width.saturating_mul(height) < i32::MAX / 4 - bf_off_bits as i32);
-
-    let row_length = ((3 * image.width + 3) / 4) * 4;
-    let size: u32 = bf_off_bits + (row_length * image.height) as u32;
-
-    let mut bmp = vec![0; size as usize];
-    bmp[0..2].copy_from_slice(b"BM");
-    bmp[2..6].copy_from_slice(&size.to_le_bytes());
-
-    bmp[10..14].copy_from_slice(&bf_off_bits.to_le_bytes());
-
-    let info_header_size: u32 = 40;
-    bmp[14..18].copy_from_slice(&info_header_size.to_le_bytes());
-
-    bmp[18..22].copy_from_slice(&width.to_le_bytes());
-    bmp[22..26].copy_from_slice(&height.to_le_bytes());
-
-    let planes: u16 = 1;
-    bmp[26..28].copy_from_slice(&planes.to_le_bytes());
-
-    let bi_bit_count: u16 = 24;
-    bmp[28..30].copy_from_slice(&bi_bit_count.to_le_bytes());
-
-    // compression = 0
-
-    for row in 0..image.height {
-        let row_start = bf_off_bits as usize + row * row_length;
-        for column in 0..image.width {
-            let start = (row_start + column * 3) as usize;
-            let pixel = &image[row][column];
-
-            bmp[start] = pixel.b;
-            bmp[start + 1] = pixel.g;
-            bmp[start + 2] = pixel.r;
-        }
-    }
-    bmp
-}
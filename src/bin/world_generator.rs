@@ -0,0 +1,32 @@
+use minecraft_clone::headless::generate::generate_region;
+use minecraft_clone::headless::{Region, parse_seed};
+use std::process::exit;
+
+const USAGE: &str = "usage: world_generator <seed> <min chunk x,y,z> <max chunk x,y,z> <output directory>\n\
+    the game loads the chunks if the output directory is saves/world-<seed>\n\
+    chunks that are already stored there, like edited ones, are kept";
+
+fn main() {
+    env_logger::init();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let [seed, min, max, output] = &args[..] else {
+        eprintln!("{USAGE}");
+        exit(2);
+    };
+    let (seed, region) = match (parse_seed(seed), Region::parse(min, max)) {
+        (Ok(seed), Ok(region)) => (seed, region),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{e}\n{USAGE}");
+            exit(2);
+        }
+    };
+
+    match generate_region(seed, region, output) {
+        Ok(report) => print!("{report}"),
+        Err(e) => {
+            eprintln!("Failed to write {output}: {e}");
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::mem::size_of;
+
+use crate::renderer::MeshData;
+use crate::renderer::mesh::{ChunkMesh, Indices, Meshing, Vertex};
+use crate::simulation::chunk::Chunk;
+use crate::simulation::position::ChunkPosition;
+use crate::simulation::world::ChunkNeighbours;
+use crate::worker::{MessageTag, WorkerMessage};
+
+/// Copies the chunk and its neighbours into a message, so that a generator worker can mesh it.
+pub fn encode_job(
+    position: ChunkPosition,
+    version: u32,
+    meshing: Meshing,
+    chunk: &Chunk,
+    neighbours: ChunkNeighbours,
+) -> Box<[u8]> {
+    let mut message = Vec::with_capacity(size_of::<[u32; 5]>() + 7 * (64 + 4097) + 1);
+    message.extend_from_slice(bytemuck::bytes_of(&position.index().to_array()));
+    message.extend_from_slice(bytemuck::bytes_of(&version));
+    message.extend_from_slice(bytemuck::bytes_of(&(meshing as u32)));
+    chunk.encode_with_light(&mut message);
+    for neighbour in [
+        neighbours.pos_x,
+        neighbours.neg_x,
+        neighbours.pos_y,
+        neighbours.neg_y,
+        neighbours.pos_z,
+        neighbours.neg_z,
+    ] {
+        neighbour.encode_with_light(&mut message);
+    }
+    message.push(MessageTag::MeshChunk as u8);
+    message.into_boxed_slice()
+}
+
+/// Meshes the chunk of a job from [`encode_job`] and returns the `MeshData` message.
+pub fn run_job(message: &WorkerMessage) -> Box<[u8]> {
+    let mut remainder = &message.bytes[..];
+    let index = *WorkerMessage::take::<[i32; 3]>(&mut remainder).unwrap();
+    let version = *WorkerMessage::take::<u32>(&mut remainder).unwrap();
+    let meshing = Meshing::from_id(*WorkerMessage::take::<u32>(&mut remainder).unwrap());
+    let chunks = [(); 7].map(|_| Chunk::decode_with_light(&mut remainder).unwrap());
+    assert_eq!(remainder.len(), 1);
+
+    let [chunk, pos_x, neg_x, pos_y, neg_y, pos_z, neg_z] = &chunks;
+    let neighbours = ChunkNeighbours {
+        pos_x,
+        neg_x,
+        pos_y,
+        neg_y,
+        pos_z,
+        neg_z,
+    };
+    let (vertices, indices) = ChunkMesh::generate(chunk, neighbours, meshing);
+    encode_mesh(index, version, &vertices, &indices)
+}
+
+/// The `MeshData` message of a mesh, `large_indices` tells the renderer the size of the indices.
+pub fn encode_mesh(
+    index: [i32; 3],
+    version: u32,
+    vertices: &[Vertex],
+    indices: &Indices,
+) -> Box<[u8]> {
+    let mesh_data = MeshData {
+        chunk: index,
+        vertex_count: vertices.len() as u32,
+        index_count: indices.len() as u32,
+        is_full_and_invisible: 0,
+        large_indices: matches!(indices, Indices::U32(_)) as u32,
+        version,
+    };
+    let mut message = Vec::new();
+    mesh_data.encode(vertices, indices, &mut message);
+    message.push(MessageTag::MeshData as u8);
+    message.into_boxed_slice()
+}
+
+#[cfg(test)]
+#[test]
+fn test_mesh_job() {
+    use crate::simulation::chunk::Block;
+    use crate::worker::WorkerId;
+    use glam::IVec3;
+
+    let mut chunk = Chunk::default();
+    chunk.set(3, 4, 5, Block::named("stone"));
+    chunk.set(3, 5, 5, Block::named("lamp"));
+    chunk.light = vec![0x12; Chunk::MAX_BLOCK_COUNT as usize];
+    let mut air = Chunk::default();
+    air.compute_transparency();
+    let neighbours = ChunkNeighbours {
+        pos_x: &air,
+        neg_x: &air,
+        pos_y: &chunk,
+        neg_y: &air,
+        pos_z: &air,
+        neg_z: &air,
+    };
+
+    let position = ChunkPosition::from_chunk_index(IVec3::new(1, -2, 3));
+    let job = encode_job(position, 7, Meshing::Simple, &chunk, neighbours);
+    let reply = run_job(&WorkerMessage {
+        sender: WorkerId::Parent,
+        bytes: job,
+    });
+    let reply = WorkerMessage {
+        sender: WorkerId::Parent,
+        bytes: reply,
+    };
+    assert_eq!(reply.tag(), MessageTag::MeshData);
+
+    let mut remainder = &reply.bytes[..];
+    let mesh_data = *WorkerMessage::take::<MeshData>(&mut remainder).unwrap();
+    let (vertices, indices) = ChunkMesh::generate(&chunk, neighbours, Meshing::Simple);
+    assert_eq!(mesh_data.chunk, [1, -2, 3]);
+    assert_eq!(mesh_data.version, 7);
+    assert_eq!(mesh_data.vertex_count as usize, vertices.len());
+    assert_eq!(mesh_data.index_count as usize, indices.len());
+    // the light has to arrive as well
+    let vertex_bytes = bytemuck::cast_slice::<_, u8>(&vertices);
+    assert_eq!(&remainder[..vertex_bytes.len()], vertex_bytes);
+    let expected = vertex_bytes.len() + indices.bytes().len();
+    assert_eq!(remainder.len(), expected.next_multiple_of(4) + 1);
+}
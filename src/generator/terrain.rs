@@ -1,6 +1,6 @@
 use crate::generator::noise::ImprovedNoise;
 use crate::simulation::chunk::{Block, Chunk};
-use crate::simulation::position::ChunkPosition;
+use crate::simulation::position::{BlockPosition, ChunkPosition};
 use crate::statistics::ChunkInfo;
 use crate::timer::Timer;
 use bytemuck::{Pod, Zeroable};
@@ -38,6 +38,16 @@ fn random(position: ChunkPosition, world_seed: WorldSeed, usage: Usage) -> StdRn
 pub struct TerrainGenerator {
     world_seed: WorldSeed,
     global_noise: ImprovedNoise,
+    blocks: TerrainBlocks,
+}
+
+/// Blocks looked up once from the registry
+struct TerrainBlocks {
+    grass: Block,
+    dirt: Block,
+    stone: Block,
+    water: Block,
+    sand: Block,
 }
 
 impl TerrainGenerator {
@@ -51,6 +61,13 @@ impl TerrainGenerator {
         Self {
             world_seed,
             global_noise,
+            blocks: TerrainBlocks {
+                grass: Block::named("grass"),
+                dirt: Block::named("dirt"),
+                stone: Block::named("stone"),
+                water: Block::named("water"),
+                sand: Block::named("sand"),
+            },
         }
     }
 
@@ -70,57 +87,107 @@ impl TerrainGenerator {
         result
     }
 
+    /// The local noise of a chunk, which makes the terrain rough.
+    fn chunk_noise(&self, position: ChunkPosition) -> ImprovedNoise {
+        let mut random = random(position, self.world_seed, Usage::FillChunk);
+        ImprovedNoise::new(&mut random)
+    }
+
+    /// Blocks with a positive density are solid. `noise` is the noise of the chunk that contains
+    /// the block.
+    fn density(&self, noise: &ImprovedNoise, block: IVec3) -> f64 {
+        let global_height = self.height(block.x as f64, block.z as f64, 4) * 40.0;
+
+        let delta_h = global_height - block.y as f64;
+        let base_density = delta_h / 127.0;
+
+        let noise = noise.noise(
+            block.x as f64 * 0.1,
+            block.y as f64 * 0.1,
+            block.z as f64 * 0.1,
+        );
+
+        base_density * (1.0 + noise.abs())
+    }
+
+    /// The block before grass is placed
+    fn block(&self, density: f64, block_y: i32) -> Block {
+        if density > 0.0 || block_y < 0 {
+            if density > 0.1 {
+                self.blocks.stone
+            } else if density > 0.0 {
+                if block_y < 1 {
+                    self.blocks.sand
+                } else {
+                    self.blocks.dirt
+                }
+            } else {
+                self.blocks.water
+            }
+        } else {
+            Block::AIR
+        }
+    }
+
+    /// The block and the density at a single position without generating the chunk, e.g. for
+    /// maps. Grass isn't placed, because it depends on the block above.
+    pub fn sample(&self, position: IVec3) -> (Block, f64) {
+        let noise = self.chunk_noise(BlockPosition::new(position).chunk());
+        let density = self.density(&noise, position);
+        (self.block(density, position.y), density)
+    }
+
+    /// The height and block of the highest block in the column that isn't air.
+    pub fn surface(&self, x: i32, z: i32) -> (i32, Block) {
+        // nothing is solid above the global height and there is water below 0
+        let global_height = self.height(x as f64, z as f64, 4) * 40.0;
+        let mut y = (global_height.ceil() as i32).max(0);
+        loop {
+            let (block, _) = self.sample(IVec3::new(x, y, z));
+            if block != Block::AIR {
+                // the block above is air, see fill_chunk for the top of chunks
+                let top_of_chunk = y.rem_euclid(Chunk::SIZE as i32) == Chunk::SIZE as i32 - 1;
+                if block == self.blocks.dirt && !top_of_chunk {
+                    return (y, self.blocks.grass);
+                }
+                return (y, block);
+            }
+            y -= 1;
+        }
+    }
+
     pub fn fill_chunk(&mut self, position: ChunkPosition) -> (Option<Chunk>, ChunkInfo) {
         let start = Timer::now();
         let mut result = Chunk::default();
 
-        let mut random = random(position, self.world_seed, Usage::FillChunk);
+        let noise = self.chunk_noise(position);
 
         let position = position.block().index();
 
-        let noise = ImprovedNoise::new(&mut random);
-        let mut non_air_block_count = 0;
-
         for x in 0..Chunk::SIZE {
             for y in 0..Chunk::SIZE {
                 for z in 0..Chunk::SIZE {
-                    let block_x = position.x + x as i32;
-                    let block_y = position.y + y as i32;
-                    let block_z = position.z + z as i32;
-
-                    let global_height = self.height(block_x as f64, block_z as f64, 4) * 40.0;
-
-                    let delta_h = global_height - block_y as f64;
-                    let base_density = delta_h / 127.0;
-
-                    let noise = noise.noise(
-                        block_x as f64 * 0.1,
-                        block_y as f64 * 0.1,
-                        block_z as f64 * 0.1,
-                    );
-
-                    let density = base_density * (1.0 + noise.abs());
-
-                    result.blocks[x][y][z] = if density > 0.0 || block_y < 0 {
-                        non_air_block_count += 1;
-                        if density > 0.1 {
-                            Block::Stone
-                        } else if density > 0.0 {
-                            if block_y < 1 {
-                                Block::Sand
-                            } else {
-                                Block::Dirt
-                            }
-                        } else {
-                            Block::Water
-                        }
-                    } else {
-                        Block::Air
-                    };
+                    let block = position + IVec3::new(x as i32, y as i32, z as i32);
+                    let density = self.density(&noise, block);
+                    result.set(x, y, z, self.block(density, block.y));
                 }
             }
         }
 
+        // the top of chunks stays dirt, grass spreads there later
+        for x in 0..Chunk::SIZE {
+            for y in 0..Chunk::SIZE - 1 {
+                for z in 0..Chunk::SIZE {
+                    if result.get(x, y, z) == self.blocks.dirt
+                        && result.get(x, y + 1, z) == Block::AIR
+                    {
+                        result.set(x, y, z, self.blocks.grass);
+                    }
+                }
+            }
+        }
+
+        let non_air_block_count = result.non_air_block_count;
         if non_air_block_count == 0 {
             return (
                 None,
@@ -131,7 +198,6 @@ impl TerrainGenerator {
             );
         }
 
-        result.non_air_block_count = non_air_block_count;
         result.compute_transparency();
 
         (
@@ -143,3 +209,38 @@ impl TerrainGenerator {
         )
     }
 }
+
+#[cfg(test)]
+#[test]
+fn test_surface() {
+    let mut generator = TerrainGenerator::new(WorldSeed(3));
+    // a column of chunks from y = -64 to 96
+    let column = IVec3::new(-2, 0, 1);
+    let chunks = (-4..6)
+        .map(|y| {
+            generator
+                .fill_chunk(ChunkPosition::from_chunk_index(column.with_y(y)))
+                .0
+        })
+        .collect::<Vec<_>>();
+    let origin = column * Chunk::SIZE as i32;
+    let get = |p: IVec3| {
+        let p = p - origin + IVec3::Y * 64;
+        let chunk = chunks[p.y as usize / Chunk::SIZE].as_ref();
+        chunk.map_or(Block::AIR, |it| {
+            it.get(p.x as usize, p.y as usize % Chunk::SIZE, p.z as usize)
+        })
+    };
+
+    for (x, z) in [(0, 0), (3, 9), (15, 15), (7, 1)] {
+        let top = (-64..96)
+            .rev()
+            .map(|y| origin + IVec3::new(x, y, z))
+            .find(|p| get(*p) != Block::AIR)
+            .unwrap();
+        assert_eq!(generator.surface(top.x, top.z), (top.y, get(top)));
+        // there is never grass below the surface
+        let below = top - IVec3::Y;
+        assert_eq!(generator.sample(below).0, get(below));
+    }
+}
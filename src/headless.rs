@@ -0,0 +1,66 @@
+use glam::IVec3;
+
+use crate::simulation::position::ChunkPosition;
+
+pub mod benchmark;
+pub mod bitmap;
+pub mod export;
+pub mod generate;
+pub mod map;
+
+/// A box of chunks for the tools that run without a window, both corners are included.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl Region {
+    pub fn new(a: IVec3, b: IVec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// Parses two corners given as chunk coordinates like `-4,0,-4`.
+    pub fn parse(a: &str, b: &str) -> Result<Self, String> {
+        Ok(Self::new(parse_chunk(a)?, parse_chunk(b)?))
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.max - self.min + 1
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.size().as_u64vec3().element_product() as usize
+    }
+
+    /// Ordered by x, then z, then y, so that columns are next to each other
+    pub(crate) fn chunks(&self) -> impl Iterator<Item = ChunkPosition> + use<> {
+        let (min, max) = (self.min, self.max);
+        (min.x..=max.x).flat_map(move |x| {
+            (min.z..=max.z).flat_map(move |z| {
+                (min.y..=max.y).map(move |y| ChunkPosition::from_chunk_index(IVec3::new(x, y, z)))
+            })
+        })
+    }
+}
+
+fn parse_chunk(value: &str) -> Result<IVec3, String> {
+    let coordinates = value
+        .split(',')
+        .map(|it| it.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid chunk {value}: {e}"))?;
+    let [x, y, z] = coordinates[..] else {
+        return Err(format!("invalid chunk {value}: expected x,y,z"));
+    };
+    Ok(IVec3::new(x, y, z))
+}
+
+pub fn parse_seed(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|e| format!("invalid seed {value}: {e}"))
+}
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hint::black_box;
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+
+use glam::IVec3;
+
+use crate::generator::meshing;
+use crate::generator::terrain::{TerrainGenerator, WorldSeed};
+use crate::renderer::MeshData;
+use crate::renderer::mesh::{ChunkMesh, Indices, Meshing};
+use crate::simulation::chunk::Chunk;
+use crate::simulation::position::ChunkPosition;
+use crate::simulation::world::ChunkNeighbours;
+
+/// Seeds and columns never change, so that results can be compared between commits.
+const SEEDS: [u64; 3] = [1, 2024, 987_654_321];
+/// Centers of 3 x 3 columns, so that the middle column is meshed with generated neighbours
+const COLUMNS: [[i32; 2]; 2] = [[0, 0], [37, -21]];
+/// From below the sea to above the mountains
+const HEIGHTS: RangeInclusive<i32> = -3..=4;
+
+/// The time that an iteration took for all items of a stage
+pub struct Stage {
+    pub name: &'static str,
+    pub items: usize,
+    pub times: Vec<Duration>,
+}
+
+pub struct BenchmarkReport {
+    pub seeds: Vec<u64>,
+    pub chunk_count: usize,
+    /// Chunks that contain blocks, only they are meshed and encoded
+    pub non_air_chunk_count: usize,
+    /// Changes when the output of the generator or the meshing changes
+    pub triangles: [(Meshing, usize); 2],
+    pub stages: Vec<Stage>,
+}
+
+/// Runs every stage `iterations` times over the same chunks. `fill_chunk` includes its own call
+/// of `compute_transparency`. Light isn't computed, like in chunks that were just generated.
+pub fn run_benchmark(iterations: usize) -> BenchmarkReport {
+    run(&SEEDS, &COLUMNS, iterations)
+}
+
+fn run(seeds: &[u64], columns: &[[i32; 2]], iterations: usize) -> BenchmarkReport {
+    let positions = positions(columns);
+    let mut stages = Vec::new();
+    let mut measure = |name, items, f: &mut dyn FnMut()| {
+        let times = (0..iterations)
+            .map(|_| {
+                let start = Instant::now();
+                f();
+                start.elapsed()
+            })
+            .collect();
+        stages.push(Stage { name, items, times });
+    };
+
+    let mut generators = (seeds.iter())
+        .map(|seed| TerrainGenerator::new(WorldSeed(*seed)))
+        .collect::<Vec<_>>();
+    let mut generated = Vec::new();
+    let chunk_count = seeds.len() * positions.len();
+    measure("fill_chunk", chunk_count, &mut || {
+        generated.clear();
+        for generator in &mut generators {
+            generated.push(
+                (positions.iter())
+                    .filter_map(|p| Some((*p, generator.fill_chunk(*p).0?)))
+                    .collect::<HashMap<_, _>>(),
+            );
+        }
+    });
+    let non_air_chunk_count = generated.iter().map(HashMap::len).sum();
+
+    measure("compute_transparency", non_air_chunk_count, &mut || {
+        for chunk in generated.iter_mut().flat_map(HashMap::values_mut) {
+            chunk.compute_transparency();
+        }
+    });
+
+    let mut air = Chunk::default();
+    air.compute_transparency();
+    let all = || {
+        generated
+            .iter()
+            .flat_map(|chunks| chunks.iter().map(move |it| (chunks, it)))
+    };
+
+    let mut triangles = [(Meshing::Greedy, 0), (Meshing::Simple, 0)];
+    let mut meshes = Vec::new();
+    for (meshing, count) in &mut triangles {
+        let name = match meshing {
+            Meshing::Greedy => "mesh_greedy",
+            Meshing::Simple => "mesh_simple",
+        };
+        measure(name, non_air_chunk_count, &mut || {
+            meshes.clear();
+            for (chunks, (position, chunk)) in all() {
+                let neighbours = neighbours(chunks, &air, *position);
+                meshes.push((*position, ChunkMesh::generate(chunk, neighbours, *meshing)));
+            }
+        });
+        *count = meshes
+            .iter()
+            .map(|(_, (_, indices))| indices.len() / 3)
+            .sum();
+    }
+
+    // the messages between the workers, see the generator and the meshing jobs
+    let mut bytes = Vec::new();
+    measure("encode_chunk", non_air_chunk_count, &mut || {
+        bytes.clear();
+        for (_, (_, chunk)) in all() {
+            chunk.encode(&mut bytes);
+        }
+    });
+    measure("decode_chunk", non_air_chunk_count, &mut || {
+        let mut remainder = &bytes[..];
+        while !remainder.is_empty() {
+            black_box(Chunk::decode(&mut remainder).unwrap());
+        }
+    });
+    measure("encode_mesh_job", non_air_chunk_count, &mut || {
+        for (chunks, (position, chunk)) in all() {
+            let neighbours = neighbours(chunks, &air, *position);
+            black_box(meshing::encode_job(
+                *position,
+                0,
+                Meshing::Greedy,
+                chunk,
+                neighbours,
+            ));
+        }
+    });
+    measure("encode_mesh_data", meshes.len(), &mut || {
+        let mut bytes = Vec::new();
+        for (position, (vertices, indices)) in &meshes {
+            bytes.clear();
+            let mesh_data = MeshData {
+                chunk: position.index().to_array(),
+                vertex_count: vertices.len() as u32,
+                index_count: indices.len() as u32,
+                is_full_and_invisible: 0,
+                large_indices: matches!(indices, Indices::U32(_)) as u32,
+                version: 0,
+            };
+            mesh_data.encode(vertices, indices, &mut bytes);
+            black_box(&bytes);
+        }
+    });
+
+    BenchmarkReport {
+        seeds: seeds.to_vec(),
+        chunk_count,
+        non_air_chunk_count,
+        triangles,
+        stages,
+    }
+}
+
+/// Chunks that weren't generated count as air
+fn neighbours<'a>(
+    chunks: &'a HashMap<ChunkPosition, Chunk>,
+    air: &'a Chunk,
+    position: ChunkPosition,
+) -> ChunkNeighbours<'a> {
+    let get = |direction| chunks.get(&position.plus(direction)).unwrap_or(air);
+    ChunkNeighbours {
+        pos_x: get(IVec3::X),
+        neg_x: get(IVec3::NEG_X),
+        pos_y: get(IVec3::Y),
+        neg_y: get(IVec3::NEG_Y),
+        pos_z: get(IVec3::Z),
+        neg_z: get(IVec3::NEG_Z),
+    }
+}
+
+fn positions(columns: &[[i32; 2]]) -> Vec<ChunkPosition> {
+    let mut positions = Vec::new();
+    for &[x, z] in columns {
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                for y in HEIGHTS {
+                    let index = IVec3::new(x + dx, y, z + dz);
+                    positions.push(ChunkPosition::from_chunk_index(index));
+                }
+            }
+        }
+    }
+    positions
+}
+
+impl BenchmarkReport {
+    /// Times are per item in microseconds, the best iteration is the least disturbed by other
+    /// processes.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+        writeln!(json, "{{").unwrap();
+        writeln!(json, "  \"profile\": \"{profile}\",").unwrap();
+        let seeds = self.seeds.iter().map(u64::to_string).collect::<Vec<_>>();
+        let seeds = seeds.join(", ");
+        writeln!(json, "  \"seeds\": [{seeds}],").unwrap();
+        let iterations = self.stages.first().map_or(0, |it| it.times.len());
+        writeln!(json, "  \"iterations\": {iterations},").unwrap();
+        writeln!(json, "  \"chunks\": {},", self.chunk_count).unwrap();
+        writeln!(json, "  \"non_air_chunks\": {},", self.non_air_chunk_count).unwrap();
+        let [(_, greedy), (_, simple)] = self.triangles;
+        writeln!(
+            json,
+            "  \"triangles\": {{\"greedy\": {greedy}, \"simple\": {simple}}},"
+        )
+        .unwrap();
+        writeln!(json, "  \"stages\": {{").unwrap();
+        for (i, stage) in self.stages.iter().enumerate() {
+            let per_item = |time: Duration| time.as_secs_f64() * 1e6 / stage.items.max(1) as f64;
+            let best = stage.times.iter().copied().min().unwrap_or_default();
+            let mean = stage.times.iter().sum::<Duration>() / stage.times.len().max(1) as u32;
+            let separator = if i + 1 < self.stages.len() { "," } else { "" };
+            writeln!(
+                json,
+                "    \"{}\": {{\"items\": {}, \"best_us\": {:.3}, \"mean_us\": {:.3}}}{separator}",
+                stage.name,
+                stage.items,
+                per_item(best),
+                per_item(mean),
+            )
+            .unwrap();
+        }
+        writeln!(json, "  }}").unwrap();
+        writeln!(json, "}}").unwrap();
+        json
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_benchmark() {
+    let report = run(&SEEDS[..1], &COLUMNS[..1], 2);
+    assert_eq!(report.chunk_count, 9 * 8);
+    assert!(report.non_air_chunk_count > 0 && report.non_air_chunk_count < report.chunk_count);
+    let [(_, greedy), (_, simple)] = report.triangles;
+    assert!(greedy > 0 && greedy < simple);
+    assert_eq!(report.stages.len(), 8);
+    assert!(report.stages.iter().all(|it| it.times.len() == 2));
+
+    let json = report.to_json();
+    assert!(json.contains(&format!("\"chunks\": {},", report.chunk_count)));
+    assert!(json.contains("\"mesh_greedy\": {\"items\": "));
+    assert!(json.trim_end().ends_with("}\n}"));
+}
@@ -0,0 +1,112 @@
+use std::ops::{Index, IndexMut};
+
+#[derive(Copy, Clone, Default)]
+pub struct Pixel {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Pixel {
+    pub fn rgb(color: u32) -> Pixel {
+        let c = color.to_le_bytes();
+        Pixel {
+            r: c[2],
+            g: c[1],
+            b: c[0],
+        }
+    }
+}
+
+/// Rows are stored bottom up, like in a bitmap file.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Pixel>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![Default::default(); width * height],
+        }
+    }
+
+    pub fn draw_image_at_offset(&mut self, other: &Image, (ox, oy): (usize, usize)) {
+        for y in 0..other.height {
+            for x in 0..other.width {
+                self[oy + y][ox + x] = other[y][x];
+            }
+        }
+    }
+}
+
+impl Index<usize> for Image {
+    type Output = [Pixel];
+
+    fn index(&self, row: usize) -> &Self::Output {
+        &self.pixels[self.width * row..][..self.width]
+    }
+}
+impl IndexMut<usize> for Image {
+    fn index_mut(&mut self, row: usize) -> &mut Self::Output {
+        &mut self.pixels[self.width * row..][..self.width]
+    }
+}
+
+pub fn encode_bitmap(image: &Image) -> Vec<u8> {
+    let width = i32::try_from(image.width).unwrap();
+    let height = i32::try_from(image.height).unwrap();
+
+    let bf_off_bits: u32 = 138;
+    assert!(width.saturating_mul(height) < i32::MAX / 4 - bf_off_bits as i32);
+
+    let row_length = (3 * image.width).div_ceil(4) * 4;
+    let size: u32 = bf_off_bits + (row_length * image.height) as u32;
+
+    let mut bmp = vec![0; size as usize];
+    bmp[0..2].copy_from_slice(b"BM");
+    bmp[2..6].copy_from_slice(&size.to_le_bytes());
+
+    bmp[10..14].copy_from_slice(&bf_off_bits.to_le_bytes());
+
+    let info_header_size: u32 = 40;
+    bmp[14..18].copy_from_slice(&info_header_size.to_le_bytes());
+
+    bmp[18..22].copy_from_slice(&width.to_le_bytes());
+    bmp[22..26].copy_from_slice(&height.to_le_bytes());
+
+    let planes: u16 = 1;
+    bmp[26..28].copy_from_slice(&planes.to_le_bytes());
+
+    let bi_bit_count: u16 = 24;
+    bmp[28..30].copy_from_slice(&bi_bit_count.to_le_bytes());
+
+    // compression = 0
+
+    for row in 0..image.height {
+        let row_start = bf_off_bits as usize + row * row_length;
+        for column in 0..image.width {
+            let start = row_start + column * 3;
+            let pixel = &image[row][column];
+
+            bmp[start] = pixel.b;
+            bmp[start + 1] = pixel.g;
+            bmp[start + 2] = pixel.r;
+        }
+    }
+    bmp
+}
+
+#[cfg(test)]
+#[test]
+fn test_bitmap() {
+    let mut image = Image::new(3, 2);
+    image[1][2] = Pixel::rgb(0x102030);
+    let bmp = encode_bitmap(&image);
+    // rows are padded to 4 bytes
+    assert_eq!(bmp.len(), 138 + 2 * 12);
+    assert_eq!(&bmp[138 + 12 + 6..][..3], &[0x30, 0x20, 0x10]);
+}
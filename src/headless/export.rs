@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::{fs, io};
+
+use glam::{IVec3, UVec2, Vec2, Vec3};
+
+use crate::generator::terrain::{TerrainGenerator, WorldSeed};
+use crate::headless::Region;
+use crate::renderer::mesh::{ChunkMesh, Indices, Meshing};
+use crate::simulation::chunk::Chunk;
+use crate::simulation::position::ChunkPosition;
+use crate::simulation::world::{ChunkNeighbours, DIRECTIONS};
+
+/// Tiles per side of the texture atlas
+const ATLAS_TILES: f32 = 4.0;
+/// The atlas is written next to the exported files, both formats reference it by this name.
+const ATLAS_FILE_NAME: &str = "blocks.bmp";
+const ATLAS: &[u8] = include_bytes!("../renderer/blocks.bmp");
+const MATERIAL_NAME: &str = "blocks";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// Wavefront OBJ with an MTL file for the material
+    Obj,
+    /// Binary glTF
+    Glb,
+}
+
+impl Format {
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "obj" => Some(Format::Obj),
+            "glb" => Some(Format::Glb),
+            _ => None,
+        }
+    }
+}
+
+/// The visible block faces of a region in world space, e.g. for Blender.
+#[derive(Default)]
+pub struct TerrainMesh {
+    positions: Vec<Vec3>,
+    /// Index into [`DIRECTIONS`] for every vertex
+    face_indices: Vec<u32>,
+    /// Relative to the whole atlas, with the origin at the top left like in `fs_main`
+    tex_coords: Vec<Vec2>,
+    indices: Vec<u32>,
+}
+
+impl TerrainMesh {
+    /// Generates the chunks of the region and meshes them. Chunks outside of the region count as
+    /// air, so the terrain is closed at the sides of the region.
+    pub fn generate(seed: u64, region: Region) -> Self {
+        let mut generator = TerrainGenerator::new(WorldSeed(seed));
+        let chunks = region
+            .chunks()
+            .filter_map(|position| Some((position, generator.fill_chunk(position).0?)))
+            .collect::<Vec<_>>();
+        Self::from_chunks(&chunks)
+    }
+
+    fn from_chunks(chunks: &[(ChunkPosition, Chunk)]) -> Self {
+        let mut air = Chunk::default();
+        air.compute_transparency();
+        let by_position = chunks
+            .iter()
+            .map(|(position, chunk)| (*position, chunk))
+            .collect::<HashMap<_, _>>();
+        let get = |position: ChunkPosition, direction: IVec3| {
+            by_position
+                .get(&position.plus(direction))
+                .copied()
+                .unwrap_or(&air)
+        };
+
+        let mut mesh = Self::default();
+        for (position, chunk) in chunks {
+            let neighbours = ChunkNeighbours {
+                pos_x: get(*position, IVec3::X),
+                neg_x: get(*position, IVec3::NEG_X),
+                pos_y: get(*position, IVec3::Y),
+                neg_y: get(*position, IVec3::NEG_Y),
+                pos_z: get(*position, IVec3::Z),
+                neg_z: get(*position, IVec3::NEG_Z),
+            };
+            // merged faces repeat their texture, which doesn't work with an atlas outside of the
+            // shader, so every face gets its own quad
+            let (vertices, indices) = ChunkMesh::generate(chunk, neighbours, Meshing::Simple);
+
+            let first = mesh.positions.len() as u32;
+            let offset = position.block().index();
+            for quad in vertices.chunks_exact(4) {
+                // the corners of a face are one tile apart in texture space
+                let min = quad
+                    .iter()
+                    .map(|it| it.tex_coord())
+                    .reduce(Vec2::min)
+                    .unwrap();
+                for vertex in quad {
+                    let tile = UVec2::from(vertex.tile()).as_vec2();
+                    mesh.positions.push((vertex.position() + offset).as_vec3());
+                    mesh.face_indices.push(vertex.face_index());
+                    mesh.tex_coords
+                        .push((tile + vertex.tex_coord() - min) / ATLAS_TILES);
+                }
+            }
+            match indices {
+                Indices::U16(it) => mesh
+                    .indices
+                    .extend(it.into_iter().map(|i| first + i as u32)),
+                Indices::U32(it) => mesh.indices.extend(it.into_iter().map(|i| first + i)),
+            }
+        }
+        mesh
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Writes the mesh and the texture atlas next to it. OBJ also gets an MTL file with the
+    /// same name.
+    pub fn store(&self, path: impl AsRef<Path>, format: Format) -> io::Result<()> {
+        let path = path.as_ref();
+        let directory = path.parent().unwrap_or(Path::new(""));
+        fs::create_dir_all(directory)?;
+        match format {
+            Format::Obj => {
+                let material_path = path.with_extension("mtl");
+                let material_file_name = material_path.file_name().unwrap().to_string_lossy();
+                fs::write(path, self.encode_obj(&material_file_name))?;
+                fs::write(&material_path, encode_mtl())?;
+            }
+            Format::Glb => {
+                let glb = self
+                    .encode_glb()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                fs::write(path, glb)?;
+            }
+        }
+        fs::write(directory.join(ATLAS_FILE_NAME), ATLAS)
+    }
+
+    /// Every vertex has its own texture coordinate, normals are shared between faces with the
+    /// same direction.
+    fn encode_obj(&self, material_file_name: &str) -> String {
+        let mut obj = String::new();
+        writeln!(obj, "mtllib {material_file_name}").unwrap();
+        writeln!(obj, "o terrain").unwrap();
+        for p in &self.positions {
+            writeln!(obj, "v {} {} {}", p.x, p.y, p.z).unwrap();
+        }
+        // OBJ has the origin at the bottom left of the texture
+        for t in &self.tex_coords {
+            writeln!(obj, "vt {} {}", t.x, 1.0 - t.y).unwrap();
+        }
+        for d in DIRECTIONS {
+            writeln!(obj, "vn {} {} {}", d.x, d.y, d.z).unwrap();
+        }
+        writeln!(obj, "usemtl {MATERIAL_NAME}").unwrap();
+        for triangle in self.indices.chunks_exact(3) {
+            obj.push('f');
+            for i in triangle {
+                let normal = self.face_indices[*i as usize] + 1;
+                write!(obj, " {v}/{v}/{normal}", v = i + 1).unwrap();
+            }
+            obj.push('\n');
+        }
+        obj
+    }
+
+    /// Binary glTF with a JSON chunk that describes the mesh and a binary chunk that contains
+    /// the positions, normals, texture coordinates and indices one after another. The image
+    /// references the BMP atlas, which Blender loads even though glTF only lists PNG and JPEG.
+    fn encode_glb(&self) -> Result<Vec<u8>, String> {
+        if self.indices.is_empty() {
+            return Err("the region doesn't contain visible blocks".to_string());
+        }
+        let positions = (self.positions.iter())
+            .flat_map(|it| it.to_array())
+            .collect::<Vec<_>>();
+        let normals = (self.face_indices.iter())
+            .flat_map(|it| DIRECTIONS[*it as usize].as_vec3().to_array())
+            .collect::<Vec<_>>();
+        let tex_coords = (self.tex_coords.iter())
+            .flat_map(|it| it.to_array())
+            .collect::<Vec<_>>();
+        let views: [&[u8]; 4] = [
+            bytemuck::cast_slice(&positions),
+            bytemuck::cast_slice(&normals),
+            bytemuck::cast_slice(&tex_coords),
+            bytemuck::cast_slice(&self.indices),
+        ];
+
+        let mut binary = Vec::new();
+        let mut buffer_views = Vec::new();
+        for (i, view) in views.iter().enumerate() {
+            // vertex attributes and indices
+            let target = if i < 3 { 34962 } else { 34963 };
+            buffer_views.push(format!(
+                r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{target}}}"#,
+                binary.len(),
+                view.len()
+            ));
+            binary.extend_from_slice(view);
+        }
+
+        let min = self.positions.iter().copied().reduce(Vec3::min).unwrap();
+        let max = self.positions.iter().copied().reduce(Vec3::max).unwrap();
+        let vertex_count = self.positions.len();
+        // 5126 is f32, 5125 is u32
+        let accessors = [
+            format!(
+                r#"{{"bufferView":0,"componentType":5126,"count":{vertex_count},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+                min.x, min.y, min.z, max.x, max.y, max.z
+            ),
+            format!(
+                r#"{{"bufferView":1,"componentType":5126,"count":{vertex_count},"type":"VEC3"}}"#
+            ),
+            format!(
+                r#"{{"bufferView":2,"componentType":5126,"count":{vertex_count},"type":"VEC2"}}"#
+            ),
+            format!(
+                r#"{{"bufferView":3,"componentType":5125,"count":{},"type":"SCALAR"}}"#,
+                self.indices.len()
+            ),
+        ];
+
+        // the sampler is nearest neighbour (9728) and clamps to the edge (33071), like the game
+        let json = format!(
+            concat!(
+                r#"{{"asset":{{"version":"2.0","generator":"minecraft-clone"}},"#,
+                r#""scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0,"name":"terrain"}}],"#,
+                r#""meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1,"TEXCOORD_0":2}},"indices":3,"material":0}}]}}],"#,
+                r#""materials":[{{"name":"{material}","pbrMetallicRoughness":{{"baseColorTexture":{{"index":0}},"metallicFactor":0,"roughnessFactor":1}}}}],"#,
+                r#""textures":[{{"sampler":0,"source":0}}],"#,
+                r#""samplers":[{{"magFilter":9728,"minFilter":9728,"wrapS":33071,"wrapT":33071}}],"#,
+                r#""images":[{{"uri":"{atlas}"}}],"#,
+                r#""buffers":[{{"byteLength":{length}}}],"bufferViews":[{views}],"accessors":[{accessors}]}}"#,
+            ),
+            material = MATERIAL_NAME,
+            atlas = ATLAS_FILE_NAME,
+            length = binary.len(),
+            views = buffer_views.join(","),
+            accessors = accessors.join(","),
+        );
+
+        let mut json = json.into_bytes();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        while binary.len() % 4 != 0 {
+            binary.push(0);
+        }
+
+        let length = 12 + 8 + json.len() + 8 + binary.len();
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        for (chunk_type, content) in [(b"JSON", &json), (b"BIN\0", &binary)] {
+            glb.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            glb.extend_from_slice(chunk_type);
+            glb.extend_from_slice(content);
+        }
+        Ok(glb)
+    }
+}
+
+fn encode_mtl() -> String {
+    format!(
+        "newmtl {MATERIAL_NAME}\nKa 1 1 1\nKd 1 1 1\nKs 0 0 0\nillum 1\nmap_Kd {ATLAS_FILE_NAME}\n"
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn test_export() {
+    use crate::simulation::chunk::Block;
+
+    let mut chunk = Chunk::default();
+    chunk.set(0, 0, 0, Block::named("stone"));
+    chunk.compute_transparency();
+    let position = ChunkPosition::from_chunk_index(IVec3::new(1, -1, 0));
+    let mesh = TerrainMesh::from_chunks(&[(position, chunk)]);
+    assert_eq!(mesh.positions.len(), 6 * 4);
+    assert_eq!(mesh.triangle_count(), 6 * 2);
+    assert!(mesh.positions.iter().all(|p| {
+        p.cmpge(Vec3::new(16.0, -16.0, 0.0)).all() && p.cmple(Vec3::new(17.0, -15.0, 1.0)).all()
+    }));
+
+    // stone is the tile at 1 1
+    let tile_min = Vec2::new(1.0, 1.0) / ATLAS_TILES;
+    let tile_max = Vec2::new(2.0, 2.0) / ATLAS_TILES;
+    for quad in mesh.tex_coords.chunks_exact(4) {
+        assert!(
+            quad.iter()
+                .all(|t| t.cmpge(tile_min).all() && t.cmple(tile_max).all())
+        );
+        assert_eq!(quad.iter().copied().reduce(Vec2::min), Some(tile_min));
+        assert_eq!(quad.iter().copied().reduce(Vec2::max), Some(tile_max));
+    }
+
+    let obj = mesh.encode_obj("terrain.mtl");
+    assert_eq!(obj.lines().filter(|it| it.starts_with("v ")).count(), 24);
+    assert_eq!(obj.lines().filter(|it| it.starts_with("f ")).count(), 12);
+    assert!(obj.contains("v 17 -15 1\n"));
+
+    let glb = mesh.encode_glb().unwrap();
+    assert_eq!(&glb[0..4], b"glTF");
+    assert_eq!(
+        u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+        glb.len()
+    );
+    let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+    let json = std::str::from_utf8(&glb[20..20 + json_length]).unwrap();
+    assert!(json.contains(r#""count":36,"type":"SCALAR""#));
+    assert!(json.contains(r#""min":[16,-16,0],"max":[17,-15,1]"#));
+    // 24 vertices with position, normal and texture coordinates, and 36 indices
+    assert_eq!(glb.len(), 20 + json_length + 8 + 24 * 32 + 36 * 4);
+
+    assert!(TerrainMesh::default().encode_glb().is_err());
+}
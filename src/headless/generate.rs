@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::generator::ChunkInfoBytes;
+use crate::generator::terrain::WorldSeed;
+use crate::headless::Region;
+use crate::simulation::MAX_REQUESTS_PER_WORKER;
+use crate::simulation::chunk::{Block, Chunk};
+use crate::simulation::position::ChunkPosition;
+use crate::simulation::region::RegionStorage;
+use crate::worker::thread_worker::ThreadWorker;
+use crate::worker::{MessageTag, Worker, WorkerId, WorkerMessage};
+
+pub struct GenerationReport {
+    pub chunk_count: usize,
+    /// Chunks that only contain air, they aren't stored
+    pub air_chunk_count: usize,
+    /// Chunks that were stored before, e.g. edited by the player, they aren't replaced
+    pub kept_chunk_count: usize,
+    pub worker_count: usize,
+    /// Wall clock time of the whole run
+    pub elapsed: Duration,
+    /// Sum of the time that the workers spent in `fill_chunk`
+    pub generation_time: Duration,
+    /// How often each block was generated, indexed by block id
+    pub histogram: Vec<u64>,
+}
+
+/// Generates the chunks of the region with one generator worker per core, like the simulation
+/// does, and stores them in the save format of the world. Storing them in `saves/world-<seed>`
+/// makes the game load them instead of the chunks it generates itself. Chunks that are already
+/// stored are kept.
+pub fn generate_region(
+    seed: u64,
+    region: Region,
+    directory: impl AsRef<Path>,
+) -> io::Result<GenerationReport> {
+    let start = Instant::now();
+    let mut storage = RegionStorage::open(directory)?;
+
+    let mut worker = ThreadWorker::new(None);
+    let worker_count = ThreadWorker::available_parallelism().get();
+    let workers = (0..worker_count)
+        .map(|_| worker.spawn_child())
+        .collect::<Vec<_>>();
+    for &w in &workers {
+        let mut message = bytemuck::bytes_of(&WorldSeed(seed)).to_vec();
+        message.push(MessageTag::InitGenerator as u8);
+        worker.send_message(w, message.into_boxed_slice());
+    }
+
+    let mut pending = region.chunks();
+    for _ in 0..MAX_REQUESTS_PER_WORKER {
+        for &w in &workers {
+            request_chunk(&worker, w, &mut pending);
+        }
+    }
+
+    let chunk_count = region.chunk_count();
+    let column_height = region.size().y as usize;
+    // chunks of columns that haven't been generated completely
+    let mut columns = HashMap::<(i32, i32), (usize, Vec<(i32, Chunk)>)>::new();
+    let mut report = GenerationReport {
+        chunk_count,
+        air_chunk_count: 0,
+        kept_chunk_count: 0,
+        worker_count,
+        elapsed: Duration::ZERO,
+        generation_time: Duration::ZERO,
+        histogram: vec![0; 256],
+    };
+    let (mut replies, mut infos) = (0, 0);
+    while replies < chunk_count || infos < chunk_count {
+        let message = worker.incoming.recv().unwrap();
+        let mut remainder = &message.bytes[..];
+        match message.tag() {
+            MessageTag::GenerateChunkReply => {
+                replies += 1;
+                request_chunk(&worker, message.sender, &mut pending);
+                let index = *WorkerMessage::take::<[i32; 3]>(&mut remainder).unwrap();
+                let chunk = Chunk::decode(&mut remainder).unwrap();
+                add_to_histogram(&mut report.histogram, &chunk);
+
+                let (received, chunks) = columns.entry((index[0], index[2])).or_default();
+                *received += 1;
+                if chunk.non_air_block_count == 0 {
+                    report.air_chunk_count += 1;
+                } else {
+                    chunks.push((index[1], chunk));
+                }
+                if *received == column_height {
+                    let (_, chunks) = columns.remove(&(index[0], index[2])).unwrap();
+                    let stored = storage.load_column(index[0], index[2])?;
+                    let chunks = (chunks.iter())
+                        .filter(|(y, _)| stored.iter().all(|(stored_y, _)| stored_y != y))
+                        .map(|(y, chunk)| (*y, chunk))
+                        .collect::<Vec<_>>();
+                    report.kept_chunk_count += stored
+                        .iter()
+                        .filter(|(y, _)| region.min.y <= *y && *y <= region.max.y)
+                        .count();
+                    if !chunks.is_empty() {
+                        storage.store_column(index[0], index[2], &chunks)?;
+                    }
+                }
+            }
+            MessageTag::ChunkInfo => {
+                infos += 1;
+                let info = WorkerMessage::take::<ChunkInfoBytes>(&mut remainder).unwrap();
+                report.generation_time += Duration::new(info.time_secs, info.time_subsec_nanos);
+            }
+            tag => log::warn!("Unexpected message {tag:?} from a generator"),
+        }
+    }
+    storage.flush()?;
+
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+/// Sends the next chunk of `pending` to the generator worker `receiver`.
+fn request_chunk(
+    worker: &ThreadWorker,
+    receiver: WorkerId,
+    pending: &mut impl Iterator<Item = ChunkPosition>,
+) {
+    if let Some(position) = pending.next() {
+        let mut message = bytemuck::bytes_of(&position.index().to_array()).to_vec();
+        message.push(MessageTag::GenerateChunk as u8);
+        worker.send_message(receiver, message.into_boxed_slice());
+    }
+}
+
+fn add_to_histogram(histogram: &mut [u64], chunk: &Chunk) {
+    if let Some(block) = chunk.single_block() {
+        histogram[block.id() as usize] += Chunk::MAX_BLOCK_COUNT as u64;
+        return;
+    }
+    for x in 0..Chunk::SIZE {
+        for y in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                histogram[chunk.get(x, y, z).id() as usize] += 1;
+            }
+        }
+    }
+}
+
+impl Display for GenerationReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        writeln!(
+            f,
+            "Generated {} chunks ({} only air, {} kept from before) on {} workers in {:.2?}, {:.0} chunks/s",
+            self.chunk_count,
+            self.air_chunk_count,
+            self.kept_chunk_count,
+            self.worker_count,
+            self.elapsed,
+            self.chunk_count as f64 / seconds
+        )?;
+        writeln!(
+            f,
+            "fill_chunk took {:.2?} in total, {:.2?} per chunk",
+            self.generation_time,
+            self.generation_time / self.chunk_count.max(1) as u32
+        )?;
+
+        let total = self.histogram.iter().sum::<u64>().max(1);
+        let mut blocks = (self.histogram.iter().enumerate())
+            .filter(|(_, count)| **count > 0)
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+        for (id, count) in blocks {
+            let name = &Block::from_id(id as u8).properties().name;
+            let percent = 100.0 * *count as f64 / total as f64;
+            writeln!(f, "{name:>12} {count:>12} {percent:>6.2}%")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_generate_region() {
+    use crate::generator::terrain::TerrainGenerator;
+    use glam::IVec3;
+
+    let directory = std::env::temp_dir().join(format!("generate-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let region = Region::new(IVec3::new(-1, -1, 0), IVec3::new(0, 2, 1));
+    // edited by the player
+    let mut edited = Chunk::default();
+    edited.set(1, 2, 3, Block::named("lamp"));
+    let mut storage = RegionStorage::open(&directory).unwrap();
+    storage.store_column(0, 1, &[(-1, &edited)]).unwrap();
+    drop(storage);
+
+    let report = generate_region(7, region, &directory).unwrap();
+    assert_eq!(report.chunk_count, 16);
+    assert_eq!(report.kept_chunk_count, 1);
+    assert!(report.air_chunk_count > 0 && report.air_chunk_count < 16);
+    assert_eq!(
+        report.histogram.iter().sum::<u64>(),
+        16 * Chunk::MAX_BLOCK_COUNT as u64
+    );
+    assert!(report.histogram[Block::named("stone").id() as usize] > 0);
+    assert!(report.to_string().contains("stone"));
+
+    let mut storage = RegionStorage::open(&directory).unwrap();
+    let mut generator = TerrainGenerator::new(WorldSeed(7));
+    let kept = storage.load_chunk(IVec3::new(0, -1, 1)).unwrap().unwrap();
+    assert_eq!(kept.get(1, 2, 3), Block::named("lamp"));
+    assert_eq!(kept.non_air_block_count, 1);
+    for position in region
+        .chunks()
+        .filter(|it| it.index() != IVec3::new(0, -1, 1))
+    {
+        let stored = storage.load_chunk(position.index()).unwrap();
+        let generated = generator.fill_chunk(position).0;
+        assert_eq!(stored.is_some(), generated.is_some());
+        if let (Some(stored), Some(generated)) = (stored, generated) {
+            assert_eq!(stored.get(3, 4, 5), generated.get(3, 4, 5));
+            assert_eq!(stored.non_air_block_count, generated.non_air_block_count);
+        }
+    }
+    assert!(storage.load_chunk(IVec3::new(0, 3, 0)).unwrap().is_none());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
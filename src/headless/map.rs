@@ -0,0 +1,140 @@
+use std::thread;
+
+use glam::{IVec2, IVec3};
+
+use crate::generator::terrain::{TerrainGenerator, WorldSeed};
+use crate::headless::bitmap::{Image, Pixel};
+use crate::simulation::chunk::Block;
+
+/// Surfaces are shaded from dark at the lower to bright at the upper height.
+const SHADED_HEIGHTS: (f32, f32) = (-64.0, 96.0);
+/// For blocks without a color
+const GREY: [u8; 3] = [128; 3];
+const SKY: [u8; 3] = [200, 225, 255];
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum View {
+    /// The surface seen from above, north (negative z) is at the top.
+    TopDown,
+    /// A vertical slice through the density field at a fixed z, blocks far away from the surface
+    /// are darker.
+    CrossSection { z: i32 },
+}
+
+/// The part of the world that a map shows
+#[derive(Copy, Clone, Debug)]
+pub struct MapArea {
+    /// Block coordinates of the center of the image, `x z` for top down maps and `x y` for
+    /// cross-sections
+    pub center: IVec2,
+    pub width: usize,
+    pub height: usize,
+    /// Every pixel covers `2^zoom` blocks along each axis, of which one is sampled.
+    pub zoom: u32,
+}
+
+/// Samples single blocks with [`TerrainGenerator::surface`] and [`TerrainGenerator::sample`]
+/// instead of generating chunks, so that large areas stay fast. Rows are split across all cores.
+pub fn render_map(seed: u64, view: View, area: MapArea) -> Image {
+    let generator = TerrainGenerator::new(WorldSeed(seed));
+    let scale = 1 << area.zoom;
+    let pixel = |row: usize, column: usize| {
+        // rows of the image go up
+        let u = area.center.x + (column as i32 - area.width as i32 / 2) * scale;
+        let v = (row as i32 - area.height as i32 / 2) * scale;
+        match view {
+            View::TopDown => top_down(&generator, u, area.center.y - v),
+            View::CrossSection { z } => {
+                cross_section(&generator, IVec3::new(u, area.center.y + v, z))
+            }
+        }
+    };
+
+    let threads = thread::available_parallelism().map_or(1, |it| it.get());
+    let rows_per_thread = area.height.div_ceil(threads).max(1);
+    let bands = thread::scope(|s| {
+        let handles = (0..area.height)
+            .step_by(rows_per_thread)
+            .map(|first| {
+                let rows = first..(first + rows_per_thread).min(area.height);
+                s.spawn(move || {
+                    rows.flat_map(|row| (0..area.width).map(move |column| pixel(row, column)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|it| it.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let mut image = Image::new(area.width, area.height);
+    let pixels = bands.into_iter().flatten();
+    for (i, pixel) in pixels.enumerate() {
+        image[i / area.width][i % area.width] = pixel;
+    }
+    image
+}
+
+fn top_down(generator: &TerrainGenerator, x: i32, z: i32) -> Pixel {
+    let (y, block) = generator.surface(x, z);
+    let (low, high) = SHADED_HEIGHTS;
+    let t = ((y as f32 - low) / (high - low)).clamp(0.0, 1.0);
+    shade(color(block), 0.5 + 0.7 * t)
+}
+
+fn cross_section(generator: &TerrainGenerator, position: IVec3) -> Pixel {
+    let (block, density) = generator.sample(position);
+    let color = if block == Block::AIR {
+        SKY
+    } else {
+        color(block)
+    };
+    shade(color, 1.0 - 0.6 * (density.abs() as f32 * 4.0).min(1.0))
+}
+
+fn color(block: Block) -> [u8; 3] {
+    block.properties().color.unwrap_or(GREY)
+}
+
+fn shade(color: [u8; 3], brightness: f32) -> Pixel {
+    let [r, g, b] = color.map(|it| (it as f32 * brightness).round().clamp(0.0, 255.0) as u8);
+    Pixel { r, g, b }
+}
+
+#[cfg(test)]
+#[test]
+fn test_map() {
+    let area = MapArea {
+        center: IVec2::new(100, -20),
+        width: 5,
+        height: 4,
+        zoom: 3,
+    };
+    let generator = TerrainGenerator::new(WorldSeed(5));
+    let rgb = |it: Pixel| [it.r, it.g, it.b];
+
+    let map = render_map(5, View::TopDown, area);
+    assert_eq!((map.width, map.height), (5, 4));
+    // column 4 is 2 * 8 blocks east of the center, row 0 is at the bottom, which is south
+    assert_eq!(rgb(map[0][4]), rgb(top_down(&generator, 116, -20 + 16)));
+    assert_eq!(rgb(map[3][0]), rgb(top_down(&generator, 84, -20 - 8)));
+
+    let section = render_map(5, View::CrossSection { z: 7 }, area);
+    assert_eq!(
+        rgb(section[1][2]),
+        rgb(cross_section(&generator, IVec3::new(100, -28, 7)))
+    );
+    // deep below the surface
+    let deep = MapArea {
+        center: IVec2::new(0, -1000),
+        ..area
+    };
+    let stone = Block::named("stone").properties().color.unwrap();
+    let section = render_map(5, View::CrossSection { z: 7 }, deep);
+    assert_eq!(
+        rgb(section[0][0]),
+        stone.map(|it| (it as f32 * 0.4).round() as u8)
+    );
+}
@@ -1,6 +1,8 @@
 extern crate core;
 
 mod generator;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
 mod renderer;
 mod simulation;
 mod statistics;
@@ -28,7 +28,7 @@ use winit::keyboard::{Key, NamedKey};
 use winit::window::{CursorGrabMode, Window, WindowId};
 
 use camera::Camera;
-use mesh::{ChunkMesh, Vertex};
+use mesh::{ChunkMesh, Indices, Vertex};
 use texture::BlockTexture;
 
 use crate::generator::ChunkInfoBytes;
@@ -85,11 +85,11 @@ pub struct RendererState {
     device: Device,
     queue: Queue,
     #[cfg(not(feature = "reload"))]
-    render_pipeline: RenderPipeline,
+    render_pipelines: RenderPipelines,
     #[cfg(feature = "reload")]
     render_pipeline_reloader: reload::Reloader,
     #[cfg(feature = "reload")]
-    render_pipeline: Option<RenderPipeline>,
+    render_pipelines: Option<RenderPipelines>,
     pipeline_layout: PipelineLayout,
     swapchain_format: TextureFormat,
     chunk_bind_group: BindGroup,
@@ -109,6 +109,8 @@ pub struct RendererState {
     is_locked: bool,
     print_statistics: bool,
     simulation: WorkerId,
+    shutdown_requested: bool,
+    shutdown_complete: bool,
     gui: Gui,
     gui_mesh: Option<GuiMesh>,
     pub window: Arc<Window>,
@@ -121,6 +123,21 @@ pub struct MeshData {
     pub vertex_count: u32,
     pub index_count: u32,
     pub is_full_and_invisible: u32,
+    /// Whether the indices are `u32` instead of `u16`
+    pub large_indices: u32,
+    /// See [`Chunk::mesh_version`](crate::simulation::chunk::Chunk::mesh_version)
+    pub version: u32,
+}
+
+impl MeshData {
+    /// Appends the mesh data with the vertices and indices, padded to 4 bytes.
+    pub fn encode(&self, vertices: &[Vertex], indices: &Indices, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(bytemuck::bytes_of(self));
+        bytes.extend_from_slice(bytemuck::cast_slice(vertices));
+        bytes.extend_from_slice(indices.bytes());
+        let padding = indices.bytes().len().next_multiple_of(4) - indices.bytes().len();
+        bytes.extend(std::iter::repeat_n(0, padding));
+    }
 }
 
 impl RendererState {
@@ -335,7 +352,7 @@ impl RendererState {
         let depth = create_depth_texture(&device, &config);
 
         #[cfg(not(feature = "reload"))]
-        let render_pipeline = create_chunk_shader_and_render_pipeline(
+        let render_pipelines = create_shader_and_render_pipelines(
             &device,
             &pipeline_layout,
             swapchain_format.into(),
@@ -345,7 +362,7 @@ impl RendererState {
         #[cfg(feature = "reload")]
         let render_pipeline_reloader = reload::Reloader::new(file!(), "renderer/shader.wgsl");
         #[cfg(feature = "reload")]
-        let render_pipeline = None;
+        let render_pipelines = None;
 
         let start = Timer::now();
         // TODO compute delta_time
@@ -359,7 +376,7 @@ impl RendererState {
             depth,
             device,
             queue,
-            render_pipeline,
+            render_pipelines,
             #[cfg(feature = "reload")]
             render_pipeline_reloader,
             pipeline_layout,
@@ -381,6 +398,8 @@ impl RendererState {
             is_locked: false,
             print_statistics: false,
             simulation,
+            shutdown_requested: false,
+            shutdown_complete: false,
             gui,
             gui_mesh: None,
             window,
@@ -398,6 +417,11 @@ impl RendererState {
             return;
         }
 
+        if self.shutdown_complete {
+            target.exit();
+            return;
+        }
+
         match event {
             WindowEvent::Resized(new_size) => {
                 self.config.width = new_size.width.max(MIN_SURFACE_SIZE);
@@ -421,9 +445,16 @@ impl RendererState {
                 // necessary on macos, according to hello triangle example
                 self.window.request_redraw();
             }
-            WindowEvent::CloseRequested => {
+            WindowEvent::CloseRequested if self.shutdown_requested => {
+                // the simulation doesn't answer, for example because it panicked
+                log::warn!("Exiting without waiting for the world to be saved");
                 target.exit();
             }
+            WindowEvent::CloseRequested => {
+                // the simulation saves the world before we exit
+                self.shutdown_requested = true;
+                worker.send_message(self.simulation, Box::new([MessageTag::Shutdown as u8]));
+            }
             WindowEvent::RedrawRequested => {
                 self.window.request_redraw();
 
@@ -456,14 +487,14 @@ impl RendererState {
 
                 #[cfg(feature = "reload")]
                 if let Some(changed) = self.render_pipeline_reloader.get_changed_content() {
-                    self.render_pipeline = match reload::validate_shader(
+                    self.render_pipelines = match reload::validate_shader(
                         changed,
                         &self.device.features(),
                         &self.device.limits(),
                         "shader.wgsl",
-                        &["vs_main", "fs_main"],
+                        &["vs_main", "vs_gui", "fs_main"],
                     ) {
-                        Ok(source) => Some(create_chunk_shader_and_render_pipeline(
+                        Ok(source) => Some(create_shader_and_render_pipelines(
                             &self.device,
                             &self.pipeline_layout,
                             self.swapchain_format.into(),
@@ -476,13 +507,13 @@ impl RendererState {
                     }
                 }
                 #[cfg(feature = "reload")]
-                let render_pipeline = if let Some(pipeline) = &self.render_pipeline {
+                let render_pipelines = if let Some(pipeline) = &self.render_pipelines {
                     pipeline
                 } else {
                     return;
                 };
                 #[cfg(not(feature = "reload"))]
-                let render_pipeline = &self.render_pipeline;
+                let render_pipelines = &self.render_pipelines;
 
                 let frame = match self.surface.get_current_texture() {
                     CurrentSurfaceTexture::Success(s) => s,
@@ -532,14 +563,14 @@ impl RendererState {
                     });
 
                     pass.push_debug_group("chunks setup");
-                    pass.set_pipeline(render_pipeline);
+                    pass.set_pipeline(&render_pipelines.chunks);
                     pass.set_bind_group(0, &self.chunk_bind_group, &[]);
                     pass.pop_debug_group();
                     pass.insert_debug_marker("before chunks");
 
                     for (position, mesh) in self.meshes.iter() {
                         pass.push_debug_group(&format!("Blocks of chunk {position:?}"));
-                        pass.set_index_buffer(mesh.index_buffer.slice(..), IndexFormat::Uint16);
+                        pass.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
                         pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
                         pass.set_bind_group(1, &mesh.bind_group, &[]);
                         pass.pop_debug_group();
@@ -579,7 +610,7 @@ impl RendererState {
                     });
 
                     pass.push_debug_group("GUI setup");
-                    pass.set_pipeline(render_pipeline);
+                    pass.set_pipeline(&render_pipelines.gui);
                     pass.set_bind_group(0, &self.ui_bind_group, &[]);
                     pass.pop_debug_group();
                     pass.insert_debug_marker("before GUI");
@@ -731,6 +762,8 @@ impl RendererState {
                 assert_eq!(remainder.len(), 1);
 
                 self.camera.position = Vec3::from(c.position);
+                self.statistics.queued_chunks = c.queued_chunks as usize;
+                self.statistics.generating_chunks = c.generating_chunks as usize;
 
                 let chunk = ChunkPosition::from_chunk_index(IVec3::from(c.player_chunk));
                 if self.player_chunk != chunk {
@@ -742,6 +775,10 @@ impl RendererState {
                     );
                 }
             }
+            Some(MessageTag::ShutdownComplete) => {
+                self.shutdown_complete = true;
+                self.window.request_redraw();
+            }
             _ => unreachable!(),
         }
     }
@@ -756,10 +793,17 @@ impl RendererState {
                 mesh_data.vertex_count as usize,
             )
             .unwrap();
-            let indices =
-                WorkerMessage::take_slice::<u16>(&mut remaining, mesh_data.index_count as usize)
-                    .unwrap();
-            if mesh_data.index_count % 2 != 0 {
+            let index_format = if mesh_data.large_indices != 0 {
+                IndexFormat::Uint32
+            } else {
+                IndexFormat::Uint16
+            };
+            let indices = WorkerMessage::take_slice::<u8>(
+                &mut remaining,
+                mesh_data.index_count as usize * index_format.byte_size() as usize,
+            )
+            .unwrap();
+            if index_format == IndexFormat::Uint16 && mesh_data.index_count % 2 != 0 {
                 WorkerMessage::take::<u16>(&mut remaining).unwrap(); // alignment
             }
 
@@ -781,6 +825,7 @@ impl RendererState {
                 position,
                 vertices,
                 indices,
+                index_format,
                 &self.chunk_bind_group_layout,
                 previous_mesh.map(|it| (it, &self.queue)),
             );
@@ -814,45 +859,58 @@ impl RendererState {
     }
 }
 
-fn create_chunk_shader_and_render_pipeline(
+/// The GUI uses the same shader, but not the packed vertices of the chunks.
+struct RenderPipelines {
+    chunks: RenderPipeline,
+    gui: RenderPipeline,
+}
+
+fn create_shader_and_render_pipelines(
     device: &Device,
     pipeline_layout: &PipelineLayout,
     target: ColorTargetState,
     source: &str,
-) -> RenderPipeline {
+) -> RenderPipelines {
     let shader = device.create_shader_module(ShaderModuleDescriptor {
         label: Some("chunks shader"),
         source: ShaderSource::Wgsl(Cow::Borrowed(source)),
     });
 
-    device.create_render_pipeline(&RenderPipelineDescriptor {
-        label: None,
-        layout: Some(pipeline_layout),
-        vertex: VertexState {
-            module: &shader,
-            entry_point: Some("vs_main"),
-            buffers: &[Some(ChunkMesh::VERTEX_BUFFER_LAYOUT)],
-            compilation_options: Default::default(),
-        },
-        fragment: Some(FragmentState {
-            module: &shader,
-            entry_point: Some("fs_main"),
-            targets: &[Some(target)],
-            compilation_options: Default::default(),
-        }),
-        primitive: PrimitiveState {
-            cull_mode: Some(Face::Back),
-            ..Default::default()
-        },
-        depth_stencil: Some(DepthStencilState {
-            format: DEPTH_TEXTURE_FORMAT,
-            depth_write_enabled: Some(true),
-            depth_compare: Some(CompareFunction::Less),
-            stencil: Default::default(),
-            bias: Default::default(),
-        }),
-        multisample: MultisampleState::default(),
-        multiview_mask: None,
-        cache: None,
-    })
+    let create = |entry_point, buffer| {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some(entry_point),
+                buffers: &[Some(buffer)],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(target.clone())],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState {
+                cull_mode: Some(Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: DEPTH_TEXTURE_FORMAT,
+                depth_write_enabled: Some(true),
+                depth_compare: Some(CompareFunction::Less),
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview_mask: None,
+            cache: None,
+        })
+    };
+
+    RenderPipelines {
+        chunks: create("vs_main", ChunkMesh::VERTEX_BUFFER_LAYOUT),
+        gui: create("vs_gui", GuiMesh::VERTEX_BUFFER_LAYOUT),
+    }
 }
@@ -13,6 +13,10 @@ pub struct Gui {
 pub enum ElementId {
     Movement,
     Center,
+    /// Held for jumping, like space
+    Jump,
+    /// Toggles flying, like `f`
+    Fly,
 }
 
 pub struct UiElement {
@@ -37,11 +41,32 @@ impl Gui {
             id: ElementId::Movement,
             center,
             size,
-            block: Block::Button,
+            block: Block::named("button"),
             visible: true,
         };
 
-        let mut elements = vec![movement];
+        // in the opposite corner, so that both thumbs can be used
+        let jump_size = 0.25 * half_size.min_element();
+        let jump = UiElement {
+            id: ElementId::Jump,
+            center: Vec2::new(half_size.x - jump_size, -half_size.y + jump_size),
+            size: jump_size,
+            block: Block::named("button"),
+            visible: true,
+        };
+        let fly_size = 0.15 * half_size.min_element();
+        let fly = UiElement {
+            id: ElementId::Fly,
+            center: Vec2::new(
+                half_size.x - jump_size,
+                -half_size.y + 2.0 * jump_size + fly_size,
+            ),
+            size: fly_size,
+            block: Block::named("button"),
+            visible: true,
+        };
+
+        let mut elements = vec![movement, jump, fly];
 
         let size = 0.01 * half_size.min_element();
         for i in -2..=2 {
@@ -49,14 +74,14 @@ impl Gui {
                 id: ElementId::Center,
                 center: Vec2::X * 2.0 * size * i as f32,
                 size,
-                block: Block::Button,
+                block: Block::named("button"),
                 visible: true,
             });
             elements.push(UiElement {
                 id: ElementId::Center,
                 center: Vec2::Y * 2.0 * size * i as f32,
                 size,
-                block: Block::Button,
+                block: Block::named("button"),
                 visible: true,
             });
         }
@@ -111,7 +136,7 @@ impl Gui {
             self.show_touch_ui = show;
             for e in self.elements.iter_mut() {
                 match e.id {
-                    ElementId::Movement => {
+                    ElementId::Movement | ElementId::Jump | ElementId::Fly => {
                         e.visible = show;
                     }
                     ElementId::Center => {}
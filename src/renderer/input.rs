@@ -1,7 +1,13 @@
 use crate::renderer::camera::Camera;
 use crate::renderer::gui::{ElementId, Gui};
+use crate::renderer::mesh::Meshing;
+use crate::simulation::chunk::Block;
 use crate::simulation::position::ChunkPosition;
-use crate::simulation::{MovementCommand, PlayerCommand};
+use crate::simulation::selection::Operation;
+use crate::simulation::{
+    DEFAULT_VIEW_DISTANCE, EditCommand, MAX_VIEW_DISTANCE, MovementCommand, PasteCommand,
+    PlayerCommand, SelectionCommand,
+};
 use crate::timer::Timer;
 use crate::worker::{MessageTag, Worker, WorkerId};
 use glam::{DVec2, Vec3};
@@ -9,14 +15,31 @@ use log::info;
 use std::mem;
 use std::time::Duration;
 use winit::event::{DeviceId, ElementState, KeyEvent, MouseButton, Touch, TouchPhase};
-use winit::keyboard::Key;
+use winit::keyboard::{Key, NamedKey};
 use winit::window::Window;
 
-#[derive(Default)]
 pub struct Input {
     controller: PlayerController,
     fingers: Vec<Finger>,
     seconds_without_touch: f32,
+    meshing: Meshing,
+    /// Horizontal view distance in chunks
+    view_distance: u32,
+    /// Applied to the selection with enter
+    operation: Operation,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            controller: PlayerController::default(),
+            fingers: Vec::new(),
+            seconds_without_touch: 0.0,
+            meshing: Meshing::default(),
+            view_distance: DEFAULT_VIEW_DISTANCE,
+            operation: Operation::Fill,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -25,10 +48,21 @@ pub struct PlayerController {
     left: f32,
     back: f32,
     right: f32,
+    jump: bool,
+    flying: bool,
+    /// Index into [`PLACEABLE_BLOCKS`]
+    selected_block: usize,
     exploding: Option<(f32, Option<(DeviceId, u64)>)>,
     creating: Option<f32>,
 }
 
+const PLACEABLE_BLOCKS: [&str; 3] = ["dirt", "lamp", "stone"];
+
+/// Name of the schematic that the clipboard is saved to and loaded from
+const CLIPBOARD_FILE_NAME: &str = "clipboard";
+/// Name of the MagicaVoxel model that is imported and exported
+const MODEL_FILE_NAME: &str = "model";
+
 struct Finger {
     id: (DeviceId, u64),
     normalized_previous_position: DVec2,
@@ -43,6 +77,8 @@ enum FingerAction {
     LongWorldTab,
     PlayerMovement,
     CameraMovement,
+    Jump,
+    ToggleFlying,
 }
 
 impl Finger {
@@ -100,6 +136,7 @@ impl Input {
                     worker,
                     simulation,
                     player_chunk,
+                    self.controller.selected_block,
                     diameter,
                     camera,
                     Some(self.fingers[index].normalized_previous_position),
@@ -116,9 +153,12 @@ impl Input {
             let delta = vectors.direction * forward + vectors.right * right;
             movement += delta;
 
-            let movement_speed = delta_time * 100.0;
             let movement_command = MovementCommand {
-                direction: (movement * movement_speed).to_array(),
+                direction: movement.to_array(),
+                view: vectors.direction.to_array(),
+                delta_time,
+                jump: self.controller.jump as u32,
+                flying: self.controller.flying as u32,
             };
             worker.send_message(simulation, {
                 let command_bytes = bytemuck::bytes_of(&movement_command);
@@ -141,7 +181,15 @@ impl Input {
                             .unwrap()
                             .normalized_previous_position
                     });
-                    send_player_command(worker, simulation, player_chunk, -20, camera, location);
+                    send_player_command(
+                        worker,
+                        simulation,
+                        player_chunk,
+                        self.controller.selected_block,
+                        -20,
+                        camera,
+                        location,
+                    );
                 }
             }
             if let Some(accumulator) = &mut self.controller.creating {
@@ -149,7 +197,15 @@ impl Input {
                 let time = 0.3;
                 if *accumulator > time {
                     *accumulator -= time;
-                    send_player_command(worker, simulation, player_chunk, 20, camera, None);
+                    send_player_command(
+                        worker,
+                        simulation,
+                        player_chunk,
+                        self.controller.selected_block,
+                        20,
+                        camera,
+                        None,
+                    );
                 }
             }
         }
@@ -164,6 +220,30 @@ impl Input {
         camera: &Camera,
         print_statistics: &mut bool,
     ) {
+        if event.logical_key == Key::Named(NamedKey::Space) {
+            self.controller.jump = event.state.is_pressed();
+        }
+        // backspace applies the operation with air, e.g. to clear the selection
+        let clear = event.logical_key == Key::Named(NamedKey::Backspace);
+        if (event.logical_key == Key::Named(NamedKey::Enter) || clear)
+            && event.state.is_pressed()
+            && !event.repeat
+        {
+            let block = if clear {
+                Block::AIR
+            } else {
+                Block::named(PLACEABLE_BLOCKS[self.controller.selected_block])
+            };
+            let command = EditCommand {
+                operation: self.operation.id(),
+                block: block.id() as u32,
+            };
+            let mut message_bytes = [0u8; mem::size_of::<EditCommand>() + 1];
+            message_bytes[0..mem::size_of::<EditCommand>()]
+                .copy_from_slice(bytemuck::bytes_of(&command));
+            *message_bytes.last_mut().unwrap() = MessageTag::EditSelection as u8;
+            worker.send_message(simulation, Box::new(message_bytes));
+        }
         if let Key::Character(str) = event.logical_key {
             let pressed = event.state.is_pressed();
             let amount = if pressed { 1.0 } else { 0.0 };
@@ -172,6 +252,90 @@ impl Input {
                 "a" => self.controller.left = amount,
                 "s" => self.controller.back = amount,
                 "d" => self.controller.right = amount,
+                "f" => self.controller.flying ^= pressed && !event.repeat,
+                "1" | "2" | "3" => {
+                    self.controller.selected_block = str.parse::<usize>().unwrap() - 1;
+                }
+                "g" if pressed && !event.repeat => {
+                    self.meshing = match self.meshing {
+                        Meshing::Simple => Meshing::Greedy,
+                        Meshing::Greedy => Meshing::Simple,
+                    };
+                    info!("meshing: {:?}", self.meshing);
+                    let mut message = [0u8; 5];
+                    message[0..4].copy_from_slice(&(self.meshing as u32).to_ne_bytes());
+                    *message.last_mut().unwrap() = MessageTag::SetMeshing as u8;
+                    worker.send_message(simulation, Box::new(message));
+                }
+                "-" | "+" | "=" if pressed => {
+                    self.view_distance = if str == "-" {
+                        self.view_distance.saturating_sub(2).max(2)
+                    } else {
+                        (self.view_distance + 2).min(MAX_VIEW_DISTANCE)
+                    };
+                    info!("view distance: {}", self.view_distance);
+                    let mut message = [0u8; 5];
+                    message[0..4].copy_from_slice(&self.view_distance.to_ne_bytes());
+                    *message.last_mut().unwrap() = MessageTag::SetViewDistance as u8;
+                    worker.send_message(simulation, Box::new(message));
+                }
+                "b" | "n" if pressed && !event.repeat => {
+                    let command = SelectionCommand {
+                        player_chunk: player_chunk.index().to_array(),
+                        position: camera.position.to_array(),
+                        direction: camera.computed_vectors().direction.to_array(),
+                        corner: (str == "n") as u32,
+                    };
+                    let mut message_bytes = [0u8; mem::size_of::<SelectionCommand>() + 1];
+                    message_bytes[0..mem::size_of::<SelectionCommand>()]
+                        .copy_from_slice(bytemuck::bytes_of(&command));
+                    *message_bytes.last_mut().unwrap() = MessageTag::SelectCorner as u8;
+                    worker.send_message(simulation, Box::new(message_bytes));
+                }
+                "c" if pressed && !event.repeat => {
+                    worker.send_message(simulation, Box::new([MessageTag::CopySelection as u8]));
+                }
+                "v" | "V" if pressed && !event.repeat => {
+                    let command = PasteCommand {
+                        player_chunk: player_chunk.index().to_array(),
+                        position: camera.position.to_array(),
+                        direction: camera.computed_vectors().direction.to_array(),
+                        with_air: (str == "v") as u32,
+                    };
+                    let mut message_bytes = [0u8; mem::size_of::<PasteCommand>() + 1];
+                    message_bytes[0..mem::size_of::<PasteCommand>()]
+                        .copy_from_slice(bytemuck::bytes_of(&command));
+                    *message_bytes.last_mut().unwrap() = MessageTag::PasteClipboard as u8;
+                    worker.send_message(simulation, Box::new(message_bytes));
+                }
+                "r" | "m" if pressed && !event.repeat => {
+                    let mut message = [0u8; 5];
+                    message[0..4].copy_from_slice(&((str == "m") as u32).to_ne_bytes());
+                    *message.last_mut().unwrap() = MessageTag::TransformClipboard as u8;
+                    worker.send_message(simulation, Box::new(message));
+                }
+                "k" | "l" | "i" | "x" if pressed && !event.repeat => {
+                    let (tag, name) = match str.as_str() {
+                        "k" => (MessageTag::SaveClipboard, CLIPBOARD_FILE_NAME),
+                        "l" => (MessageTag::LoadClipboard, CLIPBOARD_FILE_NAME),
+                        "i" => (MessageTag::ImportVox, MODEL_FILE_NAME),
+                        _ => (MessageTag::ExportVox, MODEL_FILE_NAME),
+                    };
+                    let mut message = name.as_bytes().to_vec();
+                    message.push(tag as u8);
+                    worker.send_message(simulation, message.into_boxed_slice());
+                }
+                "o" if pressed && !event.repeat => {
+                    let next = (self.operation.id() + 1) as usize % Operation::ALL.len();
+                    self.operation = Operation::ALL[next];
+                    info!("operation: {:?}", self.operation);
+                }
+                "z" if pressed && !event.repeat => {
+                    worker.send_message(simulation, Box::new([MessageTag::Undo as u8]));
+                }
+                "y" if pressed && !event.repeat => {
+                    worker.send_message(simulation, Box::new([MessageTag::Redo as u8]));
+                }
                 "p" => {
                     if pressed {
                         *print_statistics ^= true;
@@ -184,7 +348,15 @@ impl Input {
                 "q" => {
                     let accumulator = self.controller.exploding.map(|it| it.0);
                     if pressed && accumulator.is_none() {
-                        send_player_command(worker, simulation, player_chunk, -20, camera, None);
+                        send_player_command(
+                            worker,
+                            simulation,
+                            player_chunk,
+                            self.controller.selected_block,
+                            -20,
+                            camera,
+                            None,
+                        );
                     }
                     self.controller.exploding =
                         pressed.then_some((accumulator.unwrap_or(-0.1), None));
@@ -192,7 +364,15 @@ impl Input {
                 "e" => {
                     let accumulator = self.controller.creating;
                     if pressed && accumulator.is_none() {
-                        send_player_command(worker, simulation, player_chunk, 20, camera, None);
+                        send_player_command(
+                            worker,
+                            simulation,
+                            player_chunk,
+                            self.controller.selected_block,
+                            20,
+                            camera,
+                            None,
+                        );
                     }
                     self.controller.creating = pressed.then_some(accumulator.unwrap_or(0.0));
                 }
@@ -211,10 +391,26 @@ impl Input {
         button: MouseButton,
     ) {
         if state == ElementState::Pressed && button == MouseButton::Left {
-            send_player_command(worker, simulation, player_chunk, -1, camera, None);
+            send_player_command(
+                worker,
+                simulation,
+                player_chunk,
+                self.controller.selected_block,
+                -1,
+                camera,
+                None,
+            );
         }
         if state == ElementState::Pressed && button == MouseButton::Right {
-            send_player_command(worker, simulation, player_chunk, 1, camera, None);
+            send_player_command(
+                worker,
+                simulation,
+                player_chunk,
+                self.controller.selected_block,
+                1,
+                camera,
+                None,
+            );
         }
     }
 
@@ -262,6 +458,14 @@ impl Input {
                     match element.id {
                         ElementId::Movement => FingerAction::PlayerMovement,
                         ElementId::Center => FingerAction::ShortWorldTab,
+                        ElementId::Jump => {
+                            self.controller.jump = true;
+                            FingerAction::Jump
+                        }
+                        ElementId::Fly => {
+                            self.controller.flying ^= true;
+                            FingerAction::ToggleFlying
+                        }
                     }
                 } else {
                     FingerAction::ShortWorldTab
@@ -308,6 +512,11 @@ impl Input {
                 {
                     self.controller.exploding = None;
                 }
+                if finger.action == FingerAction::Jump
+                    && !self.fingers.iter().any(|f| f.action == FingerAction::Jump)
+                {
+                    self.controller.jump = false;
+                }
 
                 if finger.action == FingerAction::ShortWorldTab {
                     // TODO what if this is a long press?
@@ -317,6 +526,7 @@ impl Input {
                             worker,
                             simulation,
                             player_chunk,
+                            self.controller.selected_block,
                             1,
                             camera,
                             Some(location),
@@ -351,6 +561,7 @@ fn send_player_command(
     worker: &impl Worker,
     simulation: WorkerId,
     player_chunk: ChunkPosition,
+    selected_block: usize,
     diameter: i32,
     camera: &Camera,
     touch_location: Option<DVec2>,
@@ -364,6 +575,7 @@ fn send_player_command(
         position: position.to_array(),
         direction: direction.to_array(),
         diameter,
+        block: Block::named(PLACEABLE_BLOCKS[selected_block]).id() as u32,
     };
     info!("send_player_command: {command:?}");
 
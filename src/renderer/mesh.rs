@@ -1,18 +1,19 @@
 use crate::renderer::gui::Gui;
 use crate::simulation::chunk::{Block, Chunk, Transparency};
+use crate::simulation::light;
 use crate::simulation::position::ChunkPosition;
-use crate::simulation::world::ChunkNeighbours;
+use crate::simulation::world::{ChunkNeighbours, DIRECTIONS};
 use crate::statistics::ChunkMeshInfo;
 use crate::timer::Timer;
 use bytemuck::{Pod, Zeroable};
-use glam::Vec3;
+use glam::{IVec3, Vec2, Vec3};
 use std::mem;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
     BindGroupLayoutEntry, BindingType, Buffer, BufferAddress, BufferBindingType, BufferSize,
-    BufferUsages, Device, Queue, ShaderStages, VertexAttribute, VertexBufferLayout, VertexFormat,
-    VertexStepMode,
+    BufferUsages, Device, IndexFormat, Queue, ShaderStages, VertexAttribute, VertexBufferLayout,
+    VertexFormat, VertexStepMode,
 };
 
 pub struct ChunkMesh {
@@ -21,6 +22,7 @@ pub struct ChunkMesh {
     pub uniform_buffer: Buffer,
     pub bind_group: BindGroup,
     pub index_count: u32,
+    pub index_format: IndexFormat,
 }
 
 pub struct GuiMesh {
@@ -31,81 +33,275 @@ pub struct GuiMesh {
     pub index_count: u32,
 }
 
+/// A chunk vertex, decoded in `vs_main`. The texture coordinates follow from the position.
+///
+/// The first word contains the position relative to the chunk with 5 bits per axis, because it
+/// goes up to 16 inclusive, followed by the face index (3 bits), the ambient occlusion (2 bits)
+/// and the light (8 bits). The second word contains the tile in the texture atlas (4 bits per axis).
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
-pub struct Vertex {
+pub struct Vertex([u32; 2]);
+
+impl Vertex {
+    /// `light` is the sky light in the upper and block light in the lower 4 bits, see [`light`].
+    /// `ambient_occlusion` goes from 0 for corners between two blocks to 3 for corners without
+    /// any blocks around them.
+    fn pack(
+        position: IVec3,
+        face_index: u32,
+        ambient_occlusion: u32,
+        light: u8,
+        tile: [u8; 2],
+    ) -> Self {
+        debug_assert!(
+            position.cmpge(IVec3::ZERO).all()
+                && position.cmple(IVec3::splat(Chunk::SIZE as i32)).all()
+        );
+        debug_assert!(tile[0] < 16 && tile[1] < 16);
+        let p = position.as_uvec3();
+        Self([
+            p.x | p.y << 5
+                | p.z << 10
+                | face_index << 15
+                | ambient_occlusion << 18
+                | (light as u32) << 20,
+            tile[0] as u32 | (tile[1] as u32) << 4,
+        ])
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct GuiVertex {
     pos: [f32; 4],
     tex_coord: [f32; 2],
     face_index: u32,
+    /// Position of the texture in the atlas
+    tile: [u32; 2],
+}
+
+/// Indices are 16 bits large, unless a mesh has too many vertices for that.
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    fn new(indices: Vec<u32>, vertex_count: usize) -> Self {
+        if vertex_count <= u16::MAX as usize + 1 {
+            Indices::U16(indices.into_iter().map(|it| it as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(it) => it.len(),
+            Indices::U32(it) => it.len(),
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            Indices::U16(it) => bytemuck::cast_slice(it),
+            Indices::U32(it) => bytemuck::cast_slice(it),
+        }
+    }
+}
+
+/// How faces are turned into quads.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Meshing {
+    /// One quad per visible face
+    Simple,
+    /// Neighbouring faces that look the same are merged into larger quads
+    #[default]
+    Greedy,
+}
+
+impl Meshing {
+    pub fn from_id(id: u32) -> Self {
+        match id {
+            0 => Meshing::Simple,
+            _ => Meshing::Greedy,
+        }
+    }
+}
+
+/// A visible block face, faces that are equal can be merged.
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct Face {
+    block: Block,
+    light: u8,
+    /// For the corners in the order of [`QUADS`]
+    ambient_occlusion: [u32; 4],
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// Adds the face `face_index` of a box of `size` blocks that starts at `position`.
+    fn add_face(&mut self, face_index: u32, position: IVec3, size: IVec3, face: Face) {
+        let corners = QUADS[face_index as usize];
+        let tile = face.block.properties().tiles[face_index as usize];
+        let occlusion = face.ambient_occlusion;
+
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend((0..4).map(|k| {
+            let corner = VERTICES[corners[k]].0;
+            let corner = IVec3::new(corner[0] as i32, corner[1] as i32, corner[2] as i32);
+            Vertex::pack(
+                position + corner * size,
+                face_index,
+                occlusion[k],
+                face.light,
+                tile,
+            )
+        }));
+
+        // split the quad along the brighter diagonal, so that the darkness of one corner doesn't
+        // stretch across the whole face
+        let triangles = if occlusion[0] + occlusion[2] < occlusion[1] + occlusion[3] {
+            [1, 2, 3, 3, 0, 1]
+        } else {
+            [0, 1, 2, 2, 3, 0]
+        };
+        self.indices.extend(triangles.map(|i| i + offset));
+    }
+
+    fn finish(self) -> (Vec<Vertex>, Indices) {
+        let indices = Indices::new(self.indices, self.vertices.len());
+        (self.vertices, indices)
+    }
 }
 
 impl ChunkMesh {
     #[rustfmt::skip]
-    pub fn generate(chunk: &Chunk, neighbours: ChunkNeighbours) -> (Vec<Vertex>, Vec<u16>) {
-        let mut vertices = vec![];
-        let mut indices: Vec<u16> = vec![];
+    pub fn generate(chunk: &Chunk, neighbours: ChunkNeighbours, meshing: Meshing) -> (Vec<Vertex>, Indices) {
+        const S: usize = Chunk::SIZE;
+        const E: usize = S - 1; // end
 
-        let mut add_face = |xyz: (usize, usize, usize), face_index: u32, block: &Block, neighbour: &Block| {
-            if neighbour.transparent() {
-                let is = [
-                    [8, 9, 10, 10, 11, 8],
-                    [12, 13, 14, 14, 15, 12],
-                    [16, 17, 18, 18, 19, 16],
-                    [20, 21, 22, 22, 23, 20],
-                    [0, 1, 2, 2, 3, 0],
-                    [4, 5, 6, 6, 7, 4],
-                ][face_index as usize];
-
-                let texture = match block{
-                    Block::Air => unreachable!(),
-                    Block::Dirt => [[1, 0],[1, 0],[0, 0],[0, 1],[1, 0],[1, 0]],
-                    Block::Stone => [[1, 1],[1, 1],[1, 1],[1, 1],[1, 1],[1, 1]],
-                    Block::Button => [[0, 2],[0, 2],[0, 2],[0, 2],[0, 2],[0, 2]],
-                    Block::Water => [[1, 2],[1, 2],[1, 2],[1, 2],[1, 2],[1, 2]],
-                    Block::Sand => [[2, 0],[2, 0],[2, 0],[2, 0],[2, 0],[2, 0]],
-                }[face_index as usize];
-
-                let offset = u16::try_from(vertices.len()).unwrap();
-                indices.extend((0..6).map(|i| i + offset));
-                vertices.extend(is.iter().map(|i| {
-                    let (mut pos, mut tex_coord) = VERTICES[*i as usize];
-                    pos[0] += xyz.0 as f32;
-                    pos[1] += xyz.1 as f32;
-                    pos[2] += xyz.2 as f32;
-
-                    let u_tiles = 4.0;
-                    let v_tiles = 4.0;
-                    tex_coord[0] += texture[0] as f32;
-                    tex_coord[1] += texture[1] as f32;
-                    tex_coord[0] /= u_tiles;
-                    tex_coord[1] /= v_tiles;
-                    Vertex {
-                        pos,
-                        tex_coord,
-                        face_index,
+        // positions go from -1 to S, blocks next to an edge of the chunk count as air
+        let get = |p: IVec3| {
+            let outside = p.cmplt(IVec3::ZERO) | p.cmpge(IVec3::splat(S as i32));
+            let chunk = match outside.bitmask() {
+                0 => chunk,
+                1 => if p.x < 0 { neighbours.neg_x } else { neighbours.pos_x },
+                2 => if p.y < 0 { neighbours.neg_y } else { neighbours.pos_y },
+                4 => if p.z < 0 { neighbours.neg_z } else { neighbours.pos_z },
+                _ => return (Block::AIR, light::FULL_SKY),
+            };
+            let p = p.rem_euclid(IVec3::splat(S as i32)).as_uvec3();
+            let (x, y, z) = (p.x as usize, p.y as usize, p.z as usize);
+            (chunk.get(x, y, z), chunk.light(x, y, z))
+        };
+        let occludes = |p: IVec3| !get(p).0.transparent();
+
+        let face = |p: IVec3, face_index: u32| {
+            let block = chunk.get(p.x as usize, p.y as usize, p.z as usize);
+            if !block.solid() {
+                return None;
+            }
+            let front = p + DIRECTIONS[face_index as usize];
+            let (neighbour, light) = get(front);
+            if !block.face_visible(&neighbour) {
+                return None;
+            }
+
+            // look at the blocks around each corner in front of the face
+            let (u_axis, v_axis) = tangent_axes(face_index);
+            let ambient_occlusion = QUADS[face_index as usize].map(|i| {
+                let pos = VERTICES[i].0;
+                let mut u = IVec3::ZERO;
+                let mut v = IVec3::ZERO;
+                u[u_axis] = pos[u_axis] as i32 * 2 - 1;
+                v[v_axis] = pos[v_axis] as i32 * 2 - 1;
+                ambient_occlusion(occludes(front + u), occludes(front + v), occludes(front + u + v))
+            });
+            Some(Face { block, light, ambient_occlusion })
+        };
+
+        let mut mesh = MeshBuilder::default();
+
+        if meshing == Meshing::Greedy {
+            for face_index in 0..6 {
+                let axis = face_index as usize / 2;
+                let (u_axis, v_axis) = tangent_axes(face_index);
+                for layer in 0..S {
+                    let mut faces = [[None; S]; S];
+                    for (u, row) in faces.iter_mut().enumerate() {
+                        for (v, it) in row.iter_mut().enumerate() {
+                            let mut p = IVec3::ZERO;
+                            p[axis] = layer as i32;
+                            p[u_axis] = u as i32;
+                            p[v_axis] = v as i32;
+                            *it = face(p, face_index);
+                        }
                     }
-                }));
+
+                    for u in 0..S {
+                        for v in 0..S {
+                            let Some(current) = faces[u][v] else {
+                                continue;
+                            };
+                            // merging only keeps the look if the occlusion doesn't change along the quad
+                            let mut width = 1;
+                            if uniform_occlusion(face_index, &current, u_axis) {
+                                while u + width < S && faces[u + width][v] == Some(current) {
+                                    width += 1;
+                                }
+                            }
+                            let mut height = 1;
+                            if uniform_occlusion(face_index, &current, v_axis) {
+                                while v + height < S && (u..u + width).all(|i| faces[i][v + height] == Some(current)) {
+                                    height += 1;
+                                }
+                            }
+                            for row in &mut faces[u..u + width] {
+                                row[v..v + height].fill(None);
+                            }
+
+                            let mut p = IVec3::ZERO;
+                            p[axis] = layer as i32;
+                            p[u_axis] = u as i32;
+                            p[v_axis] = v as i32;
+                            let mut size = IVec3::ONE;
+                            size[u_axis] = width as i32;
+                            size[v_axis] = height as i32;
+                            mesh.add_face(face_index, p, size, current);
+                        }
+                    }
+                }
+            }
+            return mesh.finish();
+        }
+
+        let mut add_face = |xyz: (usize, usize, usize), face_index: u32| {
+            let p = IVec3::new(xyz.0 as i32, xyz.1 as i32, xyz.2 as i32);
+            if let Some(face) = face(p, face_index) {
+                mesh.add_face(face_index, p, IVec3::ONE, face);
             }
         };
 
-        const S: usize = Chunk::SIZE;
-        const E: usize = S - 1; // end
+        // faces between blocks of the same kind are never visible
+        let inner = if chunk.single_block().is_some() { 0 } else { S };
 
-        for x in 0..S {
+        for x in 0..inner {
             for y in 0..S {
                 for z in 0..S {
-                    let block = &chunk.blocks[x][y][z];
-                    if let Block::Air = block {
-                        continue;
-                    }
                     let xyz = (x, y, z);
 
-                    if x != E { add_face(xyz, 0, block, &chunk.blocks[x + 1][y][z]); }
-                    if x != 0 { add_face(xyz, 1, block, &chunk.blocks[x - 1][y][z]); }
-                    if y != E { add_face(xyz, 2, block, &chunk.blocks[x][y + 1][z]); }
-                    if y != 0 { add_face(xyz, 3, block, &chunk.blocks[x][y - 1][z]); }
-                    if z != E { add_face(xyz, 4, block, &chunk.blocks[x][y][z + 1]); }
-                    if z != 0 { add_face(xyz, 5, block, &chunk.blocks[x][y][z - 1]); }
+                    if x != E { add_face(xyz, 0); }
+                    if x != 0 { add_face(xyz, 1); }
+                    if y != E { add_face(xyz, 2); }
+                    if y != 0 { add_face(xyz, 3); }
+                    if z != E { add_face(xyz, 4); }
+                    if z != 0 { add_face(xyz, 5); }
                 }
             }
         }
@@ -115,15 +311,7 @@ impl ChunkMesh {
                 for x in (offset.0..S).step_by(step.0) {
                     for y in (offset.1..S).step_by(step.1) {
                         for z in (offset.2..S).step_by(step.2) {
-                            let block = &chunk.blocks[x][y][z];
-                            if let Block::Air = block {
-                                continue;
-                            }
-                            let ix = if step.0 == 1 { x } else if offset.0 == 0 { E } else { 0 };
-                            let iy = if step.1 == 1 { y } else if offset.1 == 0 { E } else { 0 };
-                            let iz = if step.2 == 1 { z } else if offset.2 == 0 { E } else { 0 };
-
-                            add_face((x, y, z), face_index, block, &neighbour.blocks[ix][iy][iz]);
+                            add_face((x, y, z), face_index);
                         }
                     }
                 }
@@ -139,21 +327,22 @@ impl ChunkMesh {
         make_chunk_face((0, 0, E), (1, 1, S), 4, neighbours.pos_z, Transparency::NegZ);
         make_chunk_face((0, 0, 0), (1, 1, S), 5, neighbours.neg_z, Transparency::PosZ);
 
-        (vertices, indices)
+        mesh.finish()
     }
 
     pub fn upload_to_gpu(
         device: &Device,
         position: ChunkPosition,
         vertices: &[Vertex],
-        indices: &[u16],
+        index_bytes: &[u8],
+        index_format: IndexFormat,
         bind_group_layout: &BindGroupLayout,
         recycle_mesh: Option<(ChunkMesh, &Queue)>,
     ) -> (ChunkMesh, ChunkMeshInfo) {
         let start = Timer::now();
 
         let vertex_bytes: &[u8] = bytemuck::cast_slice(&vertices);
-        let index_bytes: &[u8] = bytemuck::cast_slice(&indices);
+        let index_count = index_bytes.len() / index_format.byte_size() as usize;
         let uniform_data = position.block().index().extend(0);
         let uniform_bytes: &[u8] = bytemuck::cast_slice(uniform_data.as_ref());
 
@@ -222,11 +411,13 @@ impl ChunkMesh {
                 index_buffer,
                 uniform_buffer,
                 bind_group,
-                index_count: indices.len().try_into().unwrap(),
+                index_count: index_count.try_into().unwrap(),
+                index_format,
             },
             ChunkMeshInfo {
                 time: start.elapsed(),
-                face_count: indices.len() / 6,
+                face_count: index_count / 6,
+                size: vertex_bytes.len() + index_bytes.len(),
                 recycled_index_buffer,
                 recycled_vertex_buffer,
             },
@@ -236,23 +427,11 @@ impl ChunkMesh {
     pub const VERTEX_BUFFER_LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
         array_stride: mem::size_of::<Vertex>() as BufferAddress,
         step_mode: VertexStepMode::Vertex,
-        attributes: &[
-            VertexAttribute {
-                format: VertexFormat::Float32x4,
-                offset: 0,
-                shader_location: 0,
-            },
-            VertexAttribute {
-                format: VertexFormat::Float32x2,
-                offset: 4 * 4,
-                shader_location: 1,
-            },
-            VertexAttribute {
-                format: VertexFormat::Uint32,
-                offset: 4 * 4 + 2 * 4,
-                shader_location: 2,
-            },
-        ],
+        attributes: &[VertexAttribute {
+            format: VertexFormat::Uint32x2,
+            offset: 0,
+            shader_location: 0,
+        }],
     };
 
     pub const BIND_GROUP_LAYOUT_DESCRIPTOR: BindGroupLayoutDescriptor<'static> =
@@ -273,7 +452,7 @@ impl ChunkMesh {
 
 impl GuiMesh {
     #[rustfmt::skip]
-    pub fn generate(gui: &Gui) -> (Vec<Vertex>, Vec<u16>) {
+    pub fn generate(gui: &Gui) -> (Vec<GuiVertex>, Vec<u16>) {
         let mut vertices = vec![];
         let mut indices: Vec<u16> = vec![];
 
@@ -287,19 +466,12 @@ impl GuiMesh {
                 [4, 5, 6, 6, 7, 4],
             ][face_index as usize];
 
-            let texture = match block {
-                Block::Air => unreachable!(),
-                Block::Dirt => [[1, 0], [1, 0], [0, 0], [0, 1], [1, 0], [1, 0]],
-                Block::Stone => [[1, 1], [1, 1], [1, 1], [1, 1], [1, 1], [1, 1]],
-                Block::Button => [[0, 2],[0, 2],[0, 2],[0, 2],[0, 2],[0, 2]],
-                Block::Water => [[1, 2],[1, 2],[1, 2],[1, 2],[1, 2],[1, 2]],
-                Block::Sand => [[2, 0],[2, 0],[2, 0],[2, 0],[2, 0],[2, 0]],
-            }[face_index as usize];
+            let texture = block.properties().tiles[face_index as usize];
 
             let offset = u16::try_from(vertices.len()).unwrap();
             indices.extend((0..6).map(|i| i + offset));
             vertices.extend(is.iter().map(|i| {
-                let (mut pos, mut tex_coord) = VERTICES[*i as usize];
+                let (mut pos, tex_coord) = VERTICES[*i as usize];
                 pos[0] *= size;
                 pos[1] *= size;
                 pos[2] *= size;
@@ -308,16 +480,11 @@ impl GuiMesh {
                 pos[1] += xyz.y;
                 pos[2] += xyz.z;
 
-                let u_tiles = 4.0;
-                let v_tiles = 4.0;
-                tex_coord[0] += texture[0] as f32;
-                tex_coord[1] += texture[1] as f32;
-                tex_coord[0] /= u_tiles;
-                tex_coord[1] /= v_tiles;
-                Vertex {
+                GuiVertex {
                     pos,
                     tex_coord,
                     face_index,
+                    tile: texture.map(u32::from),
                 }
             }));
         };
@@ -336,7 +503,7 @@ impl GuiMesh {
     pub fn upload_to_gpu(
         device: &Device,
         position: ChunkPosition,
-        vertices: &[Vertex],
+        vertices: &[GuiVertex],
         indices: &[u16],
         bind_group_layout: &BindGroupLayout,
         recycle_mesh: Option<(GuiMesh, &Queue)>,
@@ -411,6 +578,43 @@ impl GuiMesh {
             index_count: indices.len().try_into().unwrap(),
         }
     }
+
+    pub const VERTEX_BUFFER_LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: mem::size_of::<GuiVertex>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &[
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: 0,
+                shader_location: 0,
+            },
+            VertexAttribute {
+                format: VertexFormat::Float32x2,
+                offset: 4 * 4,
+                shader_location: 1,
+            },
+            VertexAttribute {
+                format: VertexFormat::Uint32,
+                offset: 4 * 4 + 2 * 4,
+                shader_location: 2,
+            },
+            VertexAttribute {
+                format: VertexFormat::Uint32x2,
+                offset: 4 * 4 + 2 * 4 + 4,
+                shader_location: 3,
+            },
+        ],
+    };
+}
+
+/// Corners are darker for every block next to them, if both sides are blocked the corner can't
+/// be seen at all.
+fn ambient_occlusion(side_1: bool, side_2: bool, corner: bool) -> u32 {
+    if side_1 && side_2 {
+        0
+    } else {
+        3 - (side_1 as u32 + side_2 as u32 + corner as u32)
+    }
 }
 
 const fn vertex(pos: [i8; 3], tc: [i8; 2]) -> ([f32; 4], [f32; 2]) {
@@ -420,6 +624,33 @@ const fn vertex(pos: [i8; 3], tc: [i8; 2]) -> ([f32; 4], [f32; 2]) {
     )
 }
 
+/// Tangent axes of the face, they are the other two axes in cyclic order.
+fn tangent_axes(face_index: u32) -> (usize, usize) {
+    let axis = face_index as usize / 2;
+    ((axis + 1) % 3, (axis + 2) % 3)
+}
+
+/// Whether the occlusion of the face stays the same along `axis`
+fn uniform_occlusion(face_index: u32, face: &Face, axis: usize) -> bool {
+    let corners = QUADS[face_index as usize].map(|i| VERTICES[i].0);
+    (0..4).all(|a| {
+        (0..4).all(|b| {
+            let same_row = (0..3).all(|i| i == axis || corners[a][i] == corners[b][i]);
+            !same_row || face.ambient_occlusion[a] == face.ambient_occlusion[b]
+        })
+    })
+}
+
+/// Indices into [`VERTICES`] for each face, in the order of [`DIRECTIONS`]
+const QUADS: [[usize; 4]; 6] = [
+    [8, 9, 10, 11],
+    [12, 13, 14, 15],
+    [16, 17, 18, 19],
+    [20, 21, 22, 23],
+    [0, 1, 2, 3],
+    [4, 5, 6, 7],
+];
+
 const VERTICES: [([f32; 4], [f32; 2]); 24] = [
     // texture: for sides v = !y
     // POS_Z u=x
@@ -453,3 +684,185 @@ const VERTICES: [([f32; 4], [f32; 2]); 24] = [
     vertex([0, 0, 0], [1, 1]),
     vertex([1, 0, 0], [0, 1]),
 ];
+
+/// Decoding like in `vs_main`
+impl Vertex {
+    /// Relative to the chunk
+    pub fn position(&self) -> IVec3 {
+        IVec3::new(
+            self.0[0] as i32 & 31,
+            (self.0[0] >> 5) as i32 & 31,
+            (self.0[0] >> 10) as i32 & 31,
+        )
+    }
+
+    /// Index into [`DIRECTIONS`], which is also the normal of the face
+    pub fn face_index(&self) -> u32 {
+        (self.0[0] >> 15) & 7
+    }
+
+    #[cfg(test)]
+    fn ambient_occlusion(&self) -> u32 {
+        (self.0[0] >> 18) & 3
+    }
+
+    pub fn tile(&self) -> [u32; 2] {
+        [self.0[1] & 15, (self.0[1] >> 4) & 15]
+    }
+
+    /// Texture coordinates in tiles, they continue across merged faces so that the texture
+    /// repeats. Same as `tex_coord` in the shader.
+    pub fn tex_coord(&self) -> Vec2 {
+        let p = self.position().as_vec3();
+        match self.face_index() {
+            0 => Vec2::new(-p.z, -p.y),
+            1 => Vec2::new(p.z, -p.y),
+            2 => Vec2::new(p.x, p.z),
+            3 => Vec2::new(-p.x, -p.z),
+            4 => Vec2::new(p.x, -p.y),
+            _ => Vec2::new(-p.x, -p.y),
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_ambient_occlusion() {
+    let stone = Block::named("stone");
+    let air = Chunk::default();
+    let mut chunk = Chunk::default();
+    chunk.set(4, 4, 4, stone);
+    chunk.set(5, 5, 4, stone);
+    chunk.set(0, 0, 0, stone);
+    let mut neg_x = Chunk::default();
+    neg_x.set(Chunk::SIZE - 1, 1, 0, stone);
+    let neighbours = ChunkNeighbours {
+        neg_x: &neg_x,
+        ..ChunkNeighbours::all(&air)
+    };
+
+    let (vertices, _) = ChunkMesh::generate(&chunk, neighbours, Meshing::Simple);
+    let top = |x: i32, y: i32| {
+        vertices
+            .iter()
+            .filter(move |it| it.face_index() == 2 && it.position().x == x && it.position().y == y)
+            .map(|it| it.ambient_occlusion())
+            .collect::<Vec<_>>()
+    };
+
+    // the block above and next to the face darkens the corners on that side
+    assert_eq!(top(5, 5), vec![2; 2]);
+    assert_eq!(top(4, 5), vec![3; 2]);
+    // across the chunk border
+    assert_eq!(top(0, 1), vec![2; 2]);
+    assert_eq!(top(1, 1), vec![3; 2]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_greedy_meshing() {
+    let stone = Block::named("stone");
+    let mut air = Chunk::default();
+    air.compute_transparency();
+    let mut chunk = Chunk::default();
+    for x in 0..Chunk::SIZE {
+        for z in 0..Chunk::SIZE {
+            chunk.set(x, 0, z, stone);
+        }
+    }
+    let neighbours = ChunkNeighbours::all(&air);
+
+    let (vertices, indices) = ChunkMesh::generate(&chunk, neighbours, Meshing::Simple);
+    assert_eq!(indices.len() / 6, 2 * 16 * 16 + 4 * 16);
+    assert_eq!(vertices.len(), 4 * indices.len() / 6);
+
+    // one quad per side
+    let (vertices, indices) = ChunkMesh::generate(&chunk, neighbours, Meshing::Greedy);
+    assert_eq!(indices.len(), 6 * 6);
+    let mut top = vertices.iter().filter(|it| it.face_index() == 2);
+    assert!(top.any(|it| it.position() == IVec3::new(16, 1, 16)));
+
+    // a block on top changes the occlusion around it
+    chunk.set(8, 1, 8, stone);
+    let (vertices, indices) = ChunkMesh::generate(&chunk, neighbours, Meshing::Greedy);
+    assert!(indices.len() > 6 * 6 + 5 * 6);
+    let occluded = vertices
+        .iter()
+        .filter(|it| it.face_index() == 2 && it.position().y == 1 && it.ambient_occlusion() < 3);
+    assert!(occluded.clone().count() > 0);
+    assert!(
+        occluded
+            .clone()
+            .all(|it| (7..=10).contains(&it.position().x))
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_large_meshes() {
+    use crate::generator::meshing::encode_mesh;
+    use crate::renderer::MeshData;
+    use crate::worker::WorkerMessage;
+
+    let vertex = Vertex::pack(IVec3::new(16, 3, 0), 5, 2, light::FULL_SKY, [3, 1]);
+    assert_eq!(vertex.position(), IVec3::new(16, 3, 0));
+    assert_eq!(vertex.face_index(), 5);
+    assert_eq!(vertex.ambient_occlusion(), 2);
+
+    let mut air = Chunk::default();
+    air.compute_transparency();
+    let mut chunk = Chunk::default();
+    for x in 0..Chunk::SIZE {
+        for y in 0..Chunk::SIZE {
+            for z in 0..Chunk::SIZE {
+                if (x + y + z) % 2 == 0 {
+                    chunk.set(x, y, z, Block::named("stone"));
+                }
+            }
+        }
+    }
+    let neighbours = ChunkNeighbours::all(&air);
+    let (vertices, indices) = ChunkMesh::generate(&chunk, neighbours, Meshing::Greedy);
+    assert_eq!(vertices.len(), 16 * 16 * 16 / 2 * 6 * 4);
+    assert!(matches!(indices, Indices::U16(_)));
+
+    assert!(matches!(
+        Indices::new(vec![0, 1, 2], 1 << 16),
+        Indices::U16(_)
+    ));
+    assert!(matches!(
+        Indices::new(vec![0, 1, 2], (1 << 16) + 1),
+        Indices::U32(_)
+    ));
+
+    // the checkerboard has the most faces that a chunk can have, so only larger meshes than
+    // that of a single chunk need 32 bit indices
+    let mut builder = MeshBuilder::default();
+    let face = Face {
+        block: Block::named("stone"),
+        light: light::FULL_SKY,
+        ambient_occlusion: [3; 4],
+    };
+    let stones = (0..Chunk::MAX_BLOCK_COUNT as i32)
+        .map(|i| IVec3::new(i / 256, i / 16 % 16, i % 16))
+        .filter(|it| it.element_sum() % 2 == 0);
+    for position in stones {
+        for face_index in (0..6).chain(0..6) {
+            builder.add_face(face_index, position, IVec3::ONE, face);
+        }
+    }
+    let (vertices, indices) = builder.finish();
+    assert_eq!(vertices.len(), 2 * 16 * 16 * 16 / 2 * 6 * 4);
+    let Indices::U32(large) = &indices else {
+        panic!("expected 32 bit indices");
+    };
+    assert_eq!(*large.iter().max().unwrap() as usize, vertices.len() - 1);
+
+    let message = encode_mesh([0; 3], 0, &vertices, &indices);
+    let mut remainder = &message[..];
+    let mesh_data = *WorkerMessage::take::<MeshData>(&mut remainder).unwrap();
+    assert_eq!(mesh_data.large_indices, 1);
+    assert_eq!(mesh_data.index_count as usize, large.len());
+    let index_bytes = &remainder[vertices.len() * mem::size_of::<Vertex>()..];
+    assert_eq!(index_bytes.len(), 4 * large.len() + 1);
+}
@@ -1,6 +1,9 @@
 struct VertexOutput {
-    @location(0) tex_coord: vec2<f32>,
+    @location(0) tex_coord: vec2<f32>, // relative to the tile, repeats after 1
     @location(1) normal: vec3<f32>,
+    @location(2) light: vec2<f32>, // sky, block
+    @location(3) ambient_occlusion: f32,
+    @location(4) @interpolate(flat) tile: vec2<u32>,
     @builtin(position) position: vec4<f32>,
 };
 
@@ -11,17 +14,42 @@ struct VertexOutput {
 
 @group(1) @binding(0) var<uniform> chunk_position: vec4<i32>; // vec3, but webgl doesn't support that
 
+// see Vertex in mesh.rs for the layout
 @vertex
-fn vs_main(
+fn vs_main(@location(0) packed: vec2<u32>) -> VertexOutput {
+    let position = vec3<f32>((vec3<u32>(packed.x) >> vec3<u32>(0u, 5u, 10u)) & vec3<u32>(31u));
+    let face_index = (packed.x >> 15u) & 7u;
+    let ambient_occlusion = (packed.x >> 18u) & 3u;
+    let light = (packed.x >> 20u) & 255u;
+
+    var result: VertexOutput;
+    result.tex_coord = tex_coord(face_index, position);
+    result.tile = vec2<u32>(packed.y & 15u, (packed.y >> 4u) & 15u);
+    result.normal = normal(face_index);
+    result.light = vec2<f32>(f32(light >> 4u), f32(light & 15u)) / 15.0;
+    result.ambient_occlusion = f32(ambient_occlusion) / 3.0;
+
+    // this allows vertices to be relative to their chunk to avoid precision issues for large coordinates
+    var offset = vec3<i32>(chunk_position.xyz - player_chunk.xyz);
+
+    result.position = transform * vec4<f32>(position + vec3<f32>(offset), 1.0);
+    return result;
+}
+
+@vertex
+fn vs_gui(
     @location(0) position: vec4<f32>,
     @location(1) tex_coord: vec2<f32>,
     @location(2) face_index: u32,
+    @location(3) tile: vec2<u32>,
 ) -> VertexOutput {
     var result: VertexOutput;
     result.tex_coord = tex_coord;
+    result.tile = tile;
     result.normal = normal(face_index);
+    result.light = vec2<f32>(1.0, 0.0);
+    result.ambient_occlusion = 1.0;
 
-    // this allows vertices to be relative to their chunk to avoid precision issues for large coordinates
     var offset = vec4<i32>(chunk_position.xyz - player_chunk.xyz, 0);
 
     result.position = transform * (position + vec4<f32>(offset));
@@ -41,14 +69,29 @@ fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
     let normal = normalize(vertex.normal); // currently all normals should already be normalized, but do it anyway
     let diffuse = max(dot(normal, to_light), 0.0) * direction_strength * light_color;
 
-    var object_color = textureSample(t_diffuse, s_diffuse, vertex.tex_coord).xyz;
+    // merged faces repeat the texture, the gradients come from the continuous coordinates so that
+    // there are no seams where it wraps around
+    let tiles = 4.0;
+    let tile_coord = fract(vertex.tex_coord);
+    let atlas_coord = (vec2<f32>(vertex.tile) + tile_coord) / tiles;
+    let gradient = vertex.tex_coord / tiles;
+    var object_color = textureSampleGrad(t_diffuse, s_diffuse, atlas_coord, dpdx(gradient), dpdy(gradient)).xyz;
 
     let border = -1.0f; // disabled for now
-    if any(vertex.tex_coord < vec2<f32>(border, border)) || any(vertex.tex_coord > vec2<f32>(1.0, 1.0) - border) {
+    if any(tile_coord < vec2<f32>(border, border)) || any(tile_coord > vec2<f32>(1.0, 1.0) - border) {
         object_color = vec3<f32>(0.02, 0.02, 0.02);
     }
 
-    let result = (ambient + diffuse) * object_color;
+    // the sun only shines where there is sky light, every level is 20% darker than the last
+    let sky = pow(0.8, 15.0 * (1.0 - vertex.light.x));
+    let block = pow(0.8, 15.0 * (1.0 - vertex.light.y));
+    let minimum = 0.02;
+    let brightness = max((ambient + diffuse) * sky, vec3<f32>(block * 0.9 + minimum));
+
+    // fully occluded corners keep some light
+    let occlusion = 0.4 + 0.6 * vertex.ambient_occlusion;
+
+    let result = brightness * occlusion * object_color;
 
     return vec4<f32>(result, 1.0);
 }
@@ -64,3 +107,15 @@ fn normal(face_index: u32) -> vec3<f32> {
     default: { return vec3<f32>( 0.0, 0.0, 0.0);} // unreachable, could be collapsed with case 5
   }
 }
+
+// the texture repeats for every block, for the sides v points downwards
+fn tex_coord(face_index: u32, position: vec3<f32>) -> vec2<f32> {
+  switch face_index {
+    case 0u: { return vec2<f32>(-position.z, -position.y);}
+    case 1u: { return vec2<f32>( position.z, -position.y);}
+    case 2u: { return vec2<f32>( position.x,  position.z);}
+    case 3u: { return vec2<f32>(-position.x, -position.z);}
+    case 4u: { return vec2<f32>( position.x, -position.y);}
+    default: { return vec2<f32>(-position.x, -position.y);}
+  }
+}
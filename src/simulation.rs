@@ -2,32 +2,62 @@ use std::mem;
 use std::mem::size_of_val;
 use std::time::Duration;
 
-use bytemuck::{Contiguous, Pod, Zeroable};
+use bytemuck::{Pod, Zeroable};
 use glam::{IVec3, Vec3};
 
 use chunk::{Block, Chunk};
+use physics::PlayerBody;
 use position::ChunkPosition;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use region::{LevelData, RegionStorage};
+use registry::BlockRegistry;
+use schematic::Schematic;
+use selection::{Operation, Selection};
+use tick::TickClock;
 use world::World;
 
-use crate::generator::ChunkColumnElement;
 use crate::generator::terrain::WorldSeed;
+use crate::renderer::MeshData;
+use crate::renderer::mesh::{Indices, Meshing};
 use crate::worker::{MessageTag, Worker, WorkerId, WorkerMessage};
 
 pub mod chunk;
+pub mod fluid;
+pub mod grass;
+pub mod gravity;
+pub mod history;
+pub mod light;
+pub mod palette;
+pub mod physics;
 pub mod position;
+pub mod region;
+pub mod registry;
+pub mod schematic;
+pub mod selection;
+pub mod tick;
+pub mod vox;
 pub mod world;
 
 pub struct SimulationState {
     seed: WorldSeed,
     world: World,
     workers: Vec<WorkerId>,
-    worker_task_count: usize,
-    next_worker_index: usize,
+    /// Tasks that were sent to the worker at the same index and haven't been answered yet
+    worker_loads: Vec<usize>,
     player_chunk: ChunkPosition,
     player_position: Vec3,
+    player_body: PlayerBody,
+    clock: TickClock,
+    random: StdRng,
     last_world_cropping_player_chunk: ChunkPosition,
+    selection: Selection,
+    clipboard: Option<Schematic>,
 }
 
+/// Schematics are shared by all worlds.
+const SCHEMATIC_DIRECTORY: &str = "saves/schematics";
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Zeroable, Pod)]
 pub struct PlayerCommand {
@@ -35,12 +65,50 @@ pub struct PlayerCommand {
     pub position: [f32; 3],
     pub direction: [f32; 3],
     pub diameter: i32,
+    /// Id of the block that is placed
+    pub block: u32,
+}
+
+/// Selects the block that the ray hits as a corner of the selection
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Zeroable, Pod)]
+pub struct SelectionCommand {
+    pub player_chunk: [i32; 3],
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    /// 0 or 1
+    pub corner: u32,
+}
+
+/// Pastes the clipboard in front of the face that the ray hits
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Zeroable, Pod)]
+pub struct PasteCommand {
+    pub player_chunk: [i32; 3],
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    /// Whether air replaces blocks, otherwise only the other blocks are placed
+    pub with_air: u32,
+}
+
+/// Applies an [`Operation`] to the selection
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Zeroable, Pod)]
+pub struct EditCommand {
+    pub operation: u32,
+    pub block: u32,
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Zeroable, Pod)]
 pub struct MovementCommand {
+    /// Input direction with a length of at most about 1
     pub direction: [f32; 3],
+    /// Direction the camera looks in, chunks in front of it are generated first
+    pub view: [f32; 3],
+    pub delta_time: f32,
+    pub jump: u32,
+    pub flying: u32,
 }
 
 #[repr(C)]
@@ -48,16 +116,72 @@ pub struct MovementCommand {
 pub struct MovementCommandReply {
     pub player_chunk: [i32; 3],
     pub position: [f32; 3],
+    /// Chunks that wait for a generator worker
+    pub queued_chunks: u32,
+    /// Chunks that are generated right now
+    pub generating_chunks: u32,
 }
 
+/// Horizontal radius of the loaded region in chunks
+pub const DEFAULT_VIEW_DISTANCE: u32 = 12;
+pub const MAX_VIEW_DISTANCE: u32 = 32;
+
+/// The loaded region is flatter than it is wide, because the terrain is.
+fn vertical_view_distance(view_distance: u32) -> u16 {
+    (view_distance * 2 / 3).max(1) as u16
+}
+
+/// Block ids of commands come from the renderer, unknown ones are logged and ignored.
+fn registered_block(id: u32) -> Option<Block> {
+    let block = BlockRegistry::get().by_id(id);
+    if block.is_none() {
+        log::warn!("Ignoring a command with the unknown block id {id}");
+    }
+    block
+}
+
+/// Number of chunks that a generator worker gets at once
+pub(crate) const MAX_REQUESTS_PER_WORKER: usize = 2;
+
 impl SimulationState {
     pub fn initialize<W: Worker>(
         worker: &mut W,
         message: WorkerMessage,
     ) -> (Self, Option<Duration>) {
-        let seed = *bytemuck::from_bytes(&message.bytes[0..8]);
+        let seed: WorldSeed = *bytemuck::from_bytes(&message.bytes[0..8]);
+
+        let storage = if cfg!(target_arch = "wasm32") {
+            None
+        } else {
+            let directory = format!("saves/world-{}", seed.0);
+            RegionStorage::open(&directory)
+                .inspect_err(|e| log::error!("Could not open {directory}, not saving: {e}"))
+                .ok()
+        };
+        let level = storage.as_ref().and_then(|s| {
+            s.load_level()
+                .inspect_err(|e| log::error!("Could not load level: {e}"))
+                .ok()
+                .flatten()
+        });
 
-        let world = World::new(12, 16);
+        let (player_chunk, player_position) = match level {
+            Some(level) => (
+                ChunkPosition::from_chunk_index(IVec3::from(level.player_chunk)),
+                Vec3::from(level.player_position),
+            ),
+            None => (
+                ChunkPosition::from_chunk_index(IVec3::new(0, 3, 0)),
+                Vec3::new(6.0, 6.0, 6.0),
+            ),
+        };
+
+        let world = World::new(
+            DEFAULT_VIEW_DISTANCE as u16,
+            vertical_view_distance(DEFAULT_VIEW_DISTANCE),
+            player_chunk,
+            storage,
+        );
 
         let workers = (0..W::available_parallelism().get())
             .map(|_| worker.spawn_child())
@@ -65,48 +189,63 @@ impl SimulationState {
 
         workers.iter().for_each(|&w| {
             worker.send_message(w, {
-                let mut message = [0u8; 17];
+                let mut message = [0u8; 9];
                 message[0..8].copy_from_slice(bytemuck::bytes_of(&seed));
-                message[8..12].copy_from_slice(bytemuck::bytes_of(&world.highest_generated_chunk));
-                message[12..16].copy_from_slice(bytemuck::bytes_of(&world.lowest_generated_chunk));
                 *message.last_mut().unwrap() = MessageTag::InitGenerator as u8;
                 Box::new(message)
             })
         });
 
-        let player_chunk = ChunkPosition::from_chunk_index(IVec3::new(0, 3, 0));
         let mut state = SimulationState {
             seed,
             world,
+            worker_loads: vec![0; workers.len()],
             workers,
-            worker_task_count: 0,
-            next_worker_index: 0,
             player_chunk,
-            player_position: Vec3::new(6.0, 6.0, 6.0),
+            player_position,
+            player_body: PlayerBody::default(),
+            clock: TickClock::new(),
+            random: StdRng::seed_from_u64(seed.0),
             last_world_cropping_player_chunk: player_chunk,
+            selection: Selection::default(),
+            clipboard: None,
         };
 
         state.send_commands_to_workers(worker);
 
-        (state, None)
+        let timeout = state.timeout();
+        (state, timeout)
     }
 
     fn send_commands_to_workers(&mut self, worker: &impl Worker) {
-        while let Some((x, z)) = self.world.next_column_to_generate() {
-            let mut message = [0u8; 9];
-            message[0..4].copy_from_slice(&x.to_ne_bytes());
-            message[4..8].copy_from_slice(&z.to_ne_bytes());
-            *message.last_mut().unwrap() = MessageTag::GenerateColumn as u8;
+        // requests stay in the queue as long as possible, so that they are still sorted by the
+        // latest player position and can be dropped when they leave the view distance
+        while self.world.generating_count() < self.workers.len() * MAX_REQUESTS_PER_WORKER
+            && let Some(position) = self.world.next_chunk_to_generate()
+        {
+            let mut message = [0u8; 13];
+            message[0..12].copy_from_slice(bytemuck::bytes_of(position.index().as_ref()));
+            *message.last_mut().unwrap() = MessageTag::GenerateChunk as u8;
 
-            worker.send_message(self.workers[self.next_worker_index], Box::new(message));
+            worker.send_message(self.next_worker(), Box::new(message));
+        }
+    }
 
-            self.worker_task_count += 1;
+    /// Returns the worker with the fewest unanswered tasks and counts the new task.
+    fn next_worker(&mut self) -> WorkerId {
+        let (index, load) = self
+            .worker_loads
+            .iter_mut()
+            .enumerate()
+            .min_by_key(|(_, load)| **load)
+            .unwrap();
+        *load += 1;
+        self.workers[index]
+    }
 
-            self.next_worker_index += 1;
-            if self.next_worker_index >= self.workers.len() {
-                self.next_worker_index = 0;
-            }
-        }
+    fn task_finished(&mut self, worker: WorkerId) {
+        let index = self.workers.iter().position(|it| *it == worker).unwrap();
+        self.worker_loads[index] -= 1;
     }
 
     pub fn update(
@@ -119,35 +258,46 @@ impl SimulationState {
         match tag {
             Some(MessageTag::ChunkInfo) => {
                 worker.send_message(WorkerId::Parent, message.unwrap().bytes);
-                return None;
+                return self.timeout();
+            }
+            Some(MessageTag::MeshData) => {
+                let message = message.unwrap();
+                self.task_finished(message.sender);
+                let mut remainder = &message.bytes[..];
+                let mesh_data = WorkerMessage::take::<MeshData>(&mut remainder).unwrap();
+                let position = ChunkPosition::from_chunk_index(IVec3::from(mesh_data.chunk));
+
+                // otherwise the chunk changed in the meantime and a newer mesh is on its way
+                let current = self
+                    .world
+                    .get_chunk(position)
+                    .is_some_and(|it| it.mesh_version == mesh_data.version);
+                if current {
+                    worker.send_message(WorkerId::Parent, message.bytes);
+                }
+                return self.timeout();
             }
-            Some(MessageTag::GenerateColumnReply) => {
+            Some(MessageTag::GenerateChunkReply) => {
                 let message = message.unwrap();
+                self.task_finished(message.sender);
                 let mut remainder = &*message.bytes;
 
-                let x = *WorkerMessage::take::<i32>(&mut remainder).unwrap();
-                let z = *WorkerMessage::take::<i32>(&mut remainder).unwrap();
-
-                for y in self.world.lowest_generated_chunk..=self.world.highest_generated_chunk {
-                    let is_some = remainder[0];
-                    let position = ChunkPosition::from_chunk_index(IVec3::new(x, y, z));
-
-                    if is_some == 1 {
-                        let element =
-                            WorkerMessage::take::<ChunkColumnElement>(&mut remainder).unwrap();
-                        let chunk = Chunk {
-                            blocks: element.blocks.map(|it| {
-                                it.map(|it| it.map(|it| Block::from_integer(it).unwrap()))
-                            }),
-                            transparency: element.transparency,
-                            in_mesh_queue: false,
-                            non_air_block_count: element.non_air_block_count,
-                        };
-                        self.world.add_chunk(position, chunk);
-                    } else {
-                        self.world.add_air_chunk(position);
-                        remainder = &remainder[2..];
-                    }
+                let index = *WorkerMessage::take::<[i32; 3]>(&mut remainder).unwrap();
+                let position = ChunkPosition::from_chunk_index(IVec3::from(index));
+                let generated = Chunk::decode(&mut remainder).unwrap();
+                assert_eq!(remainder.len(), 1);
+
+                if !self.world.finish_generation(position) {
+                    // the player moved away while it was generated
+                    self.send_commands_to_workers(worker);
+                    return self.timeout();
+                }
+                // edits from an earlier session win over the generated chunk
+                let chunk = self.world.load_stored_chunk(position).unwrap_or(generated);
+                if chunk.non_air_block_count == 0 {
+                    self.world.add_air_chunk(position);
+                } else {
+                    self.world.add_chunk(position, chunk);
                 }
             }
             Some(MessageTag::PlayerCommand) => {
@@ -161,27 +311,39 @@ impl SimulationState {
                     ChunkPosition::from_chunk_index(IVec3::from(c.player_chunk)),
                     Vec3::from(c.position),
                     Vec3::from(c.direction),
-                    200,
+                    200.0,
                 );
 
                 let (hit, block) = if c.diameter > 0 {
-                    (hit.0, Block::Dirt)
+                    // place in front of the face that was hit, unless we are inside a block
+                    let hit = hit.filter(|it| it.normal != IVec3::ZERO);
+                    (
+                        hit.map(|it| it.block.plus(it.normal)),
+                        registered_block(c.block),
+                    )
                 } else {
-                    (hit.1, Block::Air)
+                    (hit.map(|it| it.block), Some(Block::AIR))
                 };
-                if let Some(hit) = hit {
+                if let (Some(hit), Some(block)) = (hit, block) {
+                    self.world.begin_edit();
                     let d = c.diameter.abs();
                     let r = d / 2;
                     for x in 0..d {
                         for y in 0..d {
                             for z in 0..d {
                                 let delta = IVec3::new(x, y, z) - r;
-                                if delta.length_squared() <= r * r {
-                                    self.world.set_block(hit.plus(delta), block);
+                                let position = hit.plus(delta);
+                                let unbreakable = self
+                                    .world
+                                    .get_block(position)
+                                    .is_some_and(|it| it.properties().hardness < 0.0);
+                                if delta.length_squared() <= r * r && !unbreakable {
+                                    self.world.set_block(position, block);
                                 }
                             }
                         }
                     }
+                    self.world.end_edit();
                 }
             }
             Some(MessageTag::MovementCommand) => {
@@ -190,43 +352,27 @@ impl SimulationState {
                 let c = WorkerMessage::take::<MovementCommand>(&mut remainder).unwrap();
                 assert_eq!(remainder.len(), 1);
 
-                let mut final_movement = Vec3::from(c.direction);
-                for _ in 0..10 {
-                    let (new_chunk, new_position) = self
-                        .player_chunk
-                        .normalize(self.player_position + final_movement);
-
-                    if self.world.collide(new_chunk, new_position) {
-                        break;
-                    }
-                    let mut adjust = None;
-                    for dx in (-1..=1).step_by(2) {
-                        for dy in (-2..=2).step_by(4) {
-                            for dz in (-1..=1).step_by(2) {
-                                let offset = IVec3::new(dx, dy, dz).as_vec3() * 0.3;
-
-                                let (new_chunk, new_position) =
-                                    new_chunk.normalize(new_position + offset);
-
-                                if self.world.collide(new_chunk, new_position) {
-                                    let amount = -offset * 0.1;
-                                    adjust = adjust.map(|a| a + amount).or(Some(amount));
-                                }
-                            }
-                        }
-                    }
-                    if let Some(adjust) = adjust {
-                        final_movement += adjust;
-                    } else {
-                        self.player_chunk = new_chunk;
-                        self.player_position = new_position;
-                        break;
-                    }
-                }
+                self.player_body.flying = c.flying != 0;
+                let position = self.player_body.update(
+                    &self.world,
+                    self.player_chunk,
+                    self.player_position,
+                    Vec3::from(c.direction),
+                    c.jump != 0,
+                    c.delta_time,
+                );
+                (self.player_chunk, self.player_position) = self.player_chunk.normalize(position);
+                self.world.set_generation_focus(
+                    self.player_chunk,
+                    Vec3::from(c.view),
+                    self.player_body.velocity,
+                );
 
                 let reply = MovementCommandReply {
                     player_chunk: self.player_chunk.index().to_array(),
                     position: self.player_position.to_array(),
+                    queued_chunks: self.world.queued_count() as u32,
+                    generating_chunks: self.world.generating_count() as u32,
                 };
                 worker.send_message(WorkerId::Parent, {
                     let command_bytes = bytemuck::bytes_of(&reply);
@@ -239,6 +385,179 @@ impl SimulationState {
 
                 return Some(Duration::ZERO);
             }
+            Some(MessageTag::SetMeshing) => {
+                let message = message.unwrap();
+                let mut remainder = &message.bytes[..];
+                let meshing = *WorkerMessage::take::<u32>(&mut remainder).unwrap();
+                assert_eq!(remainder.len(), 1);
+
+                self.world.set_meshing(Meshing::from_id(meshing));
+            }
+            Some(MessageTag::SetViewDistance) => {
+                let message = message.unwrap();
+                let mut remainder = &message.bytes[..];
+                let view_distance = *WorkerMessage::take::<u32>(&mut remainder).unwrap();
+                assert_eq!(remainder.len(), 1);
+
+                let view_distance = view_distance.clamp(1, MAX_VIEW_DISTANCE);
+                self.world
+                    .set_view_distance(view_distance as u16, vertical_view_distance(view_distance));
+                self.crop_world(worker);
+            }
+            Some(MessageTag::SelectCorner) => {
+                let message = message.unwrap();
+                let mut remainder = &message.bytes[..];
+                let c = WorkerMessage::take::<SelectionCommand>(&mut remainder).unwrap();
+                assert_eq!(remainder.len(), 1);
+
+                let hit = self.world.find_nearest_block_on_ray(
+                    ChunkPosition::from_chunk_index(IVec3::from(c.player_chunk)),
+                    Vec3::from(c.position),
+                    Vec3::from(c.direction),
+                    200.0,
+                );
+                if let Some(hit) = hit {
+                    log::info!("corner {}: {:?}", c.corner + 1, hit.block);
+                    self.selection.corners[c.corner as usize % 2] = Some(hit.block);
+                }
+            }
+            Some(MessageTag::EditSelection) => {
+                let message = message.unwrap();
+                let mut remainder = &message.bytes[..];
+                let c = WorkerMessage::take::<EditCommand>(&mut remainder).unwrap();
+                assert_eq!(remainder.len(), 1);
+
+                if let Some(operation) = Operation::from_id(c.operation)
+                    && let Some(block) = registered_block(c.block)
+                {
+                    self.world.begin_edit();
+                    let changed = self.selection.apply(&mut self.world, operation, block);
+                    self.world.end_edit();
+                    log::info!("{operation:?}: {changed:?} blocks changed");
+                }
+            }
+            Some(MessageTag::CopySelection) => {
+                if let Some(schematic) = self.copy_selection() {
+                    log::info!("copied {} blocks", schematic.size());
+                    self.clipboard = Some(schematic);
+                }
+            }
+            Some(MessageTag::TransformClipboard) => {
+                let message = message.unwrap();
+                let mut remainder = &message.bytes[..];
+                let mirror = *WorkerMessage::take::<u32>(&mut remainder).unwrap() != 0;
+                assert_eq!(remainder.len(), 1);
+
+                if let Some(clipboard) = &mut self.clipboard {
+                    *clipboard = if mirror {
+                        clipboard.mirrored()
+                    } else {
+                        clipboard.rotated()
+                    };
+                }
+            }
+            Some(MessageTag::PasteClipboard) => {
+                let message = message.unwrap();
+                let mut remainder = &message.bytes[..];
+                let c = WorkerMessage::take::<PasteCommand>(&mut remainder).unwrap();
+                assert_eq!(remainder.len(), 1);
+
+                let hit = self.world.find_nearest_block_on_ray(
+                    ChunkPosition::from_chunk_index(IVec3::from(c.player_chunk)),
+                    Vec3::from(c.position),
+                    Vec3::from(c.direction),
+                    200.0,
+                );
+                if let (Some(hit), Some(clipboard)) = (hit, &self.clipboard) {
+                    self.world.begin_edit();
+                    let changed = clipboard.paste(
+                        &mut self.world,
+                        hit.block.plus(hit.normal),
+                        c.with_air != 0,
+                    );
+                    self.world.end_edit();
+                    log::info!("pasted, {changed} blocks changed");
+                }
+            }
+            Some(
+                tag @ (MessageTag::SaveClipboard
+                | MessageTag::LoadClipboard
+                | MessageTag::ImportVox
+                | MessageTag::ExportVox),
+            ) => {
+                let message = message.unwrap();
+                let name = std::str::from_utf8(&message.bytes[..message.bytes.len() - 1]);
+                let Some(name) = name.ok().filter(|name| {
+                    !name.is_empty()
+                        && name
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                }) else {
+                    log::error!("Invalid schematic name");
+                    return self.timeout();
+                };
+                let vox = matches!(tag, MessageTag::ImportVox | MessageTag::ExportVox);
+                let extension = if vox { "vox" } else { "schematic" };
+                let path = format!("{SCHEMATIC_DIRECTORY}/{name}.{extension}");
+
+                match tag {
+                    MessageTag::SaveClipboard | MessageTag::ExportVox => {
+                        // models are exported from the selection, so they don't replace the clipboard
+                        let result = if vox {
+                            self.copy_selection().map(|it| vox::store(&it, &path))
+                        } else {
+                            self.clipboard.as_ref().map(|it| it.store(&path))
+                        };
+                        match result {
+                            Some(Ok(())) => log::info!("stored {path}"),
+                            Some(Err(e)) => log::error!("Could not store {path}: {e}"),
+                            None => log::info!("Nothing to store"),
+                        }
+                    }
+                    _ => {
+                        let result = if vox {
+                            vox::load(&path)
+                        } else {
+                            Schematic::load(&path)
+                        };
+                        match result {
+                            Ok(schematic) => {
+                                log::info!("loaded {path} with a size of {}", schematic.size());
+                                self.clipboard = Some(schematic);
+                            }
+                            Err(e) => log::error!("Could not load {path}: {e}"),
+                        }
+                    }
+                }
+            }
+            Some(MessageTag::Undo) => {
+                if let Err(e) = self.world.undo() {
+                    log::info!("Can't undo: {e}");
+                }
+            }
+            Some(MessageTag::Redo) => {
+                if let Err(e) = self.world.redo() {
+                    log::info!("Can't redo: {e}");
+                }
+            }
+            Some(MessageTag::Shutdown) => {
+                self.world.save();
+                if let Some(storage) = self.world.storage() {
+                    let level = LevelData {
+                        seed: self.seed.0,
+                        player_chunk: self.player_chunk.index().to_array(),
+                        player_position: self.player_position.to_array(),
+                    };
+                    if let Err(e) = storage.store_level(&level) {
+                        log::error!("Could not store level: {e}");
+                    }
+                }
+                worker.send_message(
+                    WorkerId::Parent,
+                    Box::new([MessageTag::ShutdownComplete as u8]),
+                );
+                return None;
+            }
             Some(t) => unreachable!("Unknown message {t:?}"),
             None => {}
         }
@@ -252,51 +571,66 @@ impl SimulationState {
             .distance_squared(self.last_world_cropping_player_chunk.index())
             >= 4
         {
-            self.last_world_cropping_player_chunk = self.player_chunk;
-            let deleted = self.world.crop(self.player_chunk);
-            if deleted.len() > 0 {
-                let total_size = deleted.len() * mem::size_of::<[i32; 3]>();
-
-                let mut message = Vec::with_capacity(total_size + 1);
-                for position in deleted {
-                    message.extend_from_slice(bytemuck::bytes_of(position.index().as_ref()));
-                }
-                debug_assert_eq!(message.len(), total_size);
-                message.push(MessageTag::ChunkRemoval as u8);
+            self.crop_world(worker);
+        }
 
-                let message = message.into_boxed_slice();
-                worker.send_message(WorkerId::Parent, message);
+        self.world.process_neighbour_updates();
+        for _ in 0..self.clock.due() {
+            self.world.tick(&mut self.random);
+        }
+
+        self.world.update_light();
+        let (empty_meshes, mesh_jobs) = self.world.get_updated_meshes();
+        if empty_meshes.len() > 0 {
+            let mut message = Vec::with_capacity(size_of_val(empty_meshes.as_slice()) + 1);
+            for mesh_data in empty_meshes {
+                mesh_data.encode(&[], &Indices::U16(vec![]), &mut message);
             }
+            message.push(MessageTag::MeshData as u8);
+            worker.send_message(WorkerId::Parent, message.into_boxed_slice());
+        }
+        for job in mesh_jobs {
+            worker.send_message(self.next_worker(), job);
         }
 
-        let meshes = self.world.get_updated_meshes();
-        if meshes.len() > 0 {
-            let total_size = meshes
-                .iter()
-                .map(|it| {
-                    size_of_val(&it.0)
-                        + size_of_val(it.1.as_slice())
-                        + size_of_val(it.2.as_slice())
-                        + if it.2.len() % 2 == 0 { 0 } else { 2 }
-                })
-                .sum::<usize>();
+        self.timeout()
+    }
+
+    /// The blocks between the corners of the selection, if both are set and it isn't too large
+    fn copy_selection(&self) -> Option<Schematic> {
+        let [Some(a), Some(b)] = self.selection.corners else {
+            log::info!("Select two corners first");
+            return None;
+        };
+        let size = ((a.index() - b.index()).abs() + 1).as_u64vec3();
+        if size.element_product() > selection::MAX_VOLUME {
+            log::warn!("Selection with a size of {size} is too large to copy");
+            return None;
+        }
+        Some(Schematic::copy(&self.world, a, b))
+    }
+
+    /// Unloads the chunks that are out of view and tells the renderer to drop their meshes.
+    fn crop_world(&mut self, worker: &impl Worker) {
+        self.last_world_cropping_player_chunk = self.player_chunk;
+        let deleted = self.world.crop(self.player_chunk);
+        if deleted.len() > 0 {
+            let total_size = deleted.len() * mem::size_of::<[i32; 3]>();
 
             let mut message = Vec::with_capacity(total_size + 1);
-            for (mesh_data, vertices, indices) in meshes {
-                message.extend_from_slice(bytemuck::bytes_of(&mesh_data));
-                message.extend_from_slice(bytemuck::cast_slice(&vertices));
-                message.extend_from_slice(bytemuck::cast_slice(&indices));
-                if indices.len() % 2 != 0 {
-                    message.extend_from_slice(&[0, 0]); // align
-                }
+            for position in deleted {
+                message.extend_from_slice(bytemuck::bytes_of(position.index().as_ref()));
             }
             debug_assert_eq!(message.len(), total_size);
-            message.push(MessageTag::MeshData as u8);
+            message.push(MessageTag::ChunkRemoval as u8);
 
             let message = message.into_boxed_slice();
             worker.send_message(WorkerId::Parent, message);
         }
+    }
 
-        None
+    /// Wakes the worker up for the next tick.
+    fn timeout(&self) -> Option<Duration> {
+        Some(self.clock.until_next())
     }
 }
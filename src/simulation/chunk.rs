@@ -1,11 +1,25 @@
-use bytemuck::Contiguous;
+use std::fmt::{Debug, Formatter};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::simulation::light;
+use crate::simulation::palette::PalettedBlocks;
+use crate::simulation::registry::{BlockProperties, BlockRegistry};
 
 #[derive(Clone)]
 pub struct Chunk {
-    pub blocks: [[[Block; Chunk::SIZE]; Chunk::SIZE]; Chunk::SIZE],
+    blocks: PalettedBlocks,
     pub transparency: u8,
     pub in_mesh_queue: bool,
     pub non_air_block_count: u16,
+    /// Set by block changes, so that the chunk is stored when it is evicted.
+    pub modified: bool,
+    /// See [`light`](crate::simulation::light). Empty until it is computed, which counts as full
+    /// sky light everywhere.
+    pub light: Vec<u8>,
+    pub in_light_queue: bool,
+    /// Changes whenever a new mesh is requested, so that outdated meshes can be recognized.
+    pub mesh_version: u32,
 }
 
 pub enum Transparency {
@@ -23,13 +37,88 @@ impl Chunk {
 
     pub const MAX_BLOCK_COUNT: u16 = Chunk::SIZE.pow(3) as u16;
 
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Block {
+        self.blocks.get(Self::index(x, y, z))
+    }
+
+    /// Returns the previous block and keeps `non_air_block_count` up to date.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: Block) -> Block {
+        let previous = self.blocks.set(Self::index(x, y, z), block);
+        if previous != block {
+            if previous == Block::AIR {
+                assert!(self.non_air_block_count < Chunk::MAX_BLOCK_COUNT);
+                self.non_air_block_count += 1;
+            }
+            if block == Block::AIR {
+                assert!(self.non_air_block_count > 0);
+                self.non_air_block_count -= 1;
+            }
+        }
+        previous
+    }
+
+    pub fn light(&self, x: usize, y: usize, z: usize) -> u8 {
+        if self.light.is_empty() {
+            light::FULL_SKY
+        } else {
+            self.light[Self::index(x, y, z)]
+        }
+    }
+
+    /// Returns the block if the whole chunk consists of it.
+    pub fn single_block(&self) -> Option<Block> {
+        self.blocks.single()
+    }
+
+    /// The encoded size is a multiple of 4 bytes, so that the message stays aligned.
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        self.blocks.encode(bytes);
+    }
+
+    pub fn decode(bytes: &mut &[u8]) -> Option<Chunk> {
+        let blocks = PalettedBlocks::decode(bytes)?;
+        let non_air_block_count =
+            (Chunk::MAX_BLOCK_COUNT as usize - blocks.count(Block::AIR)) as u16;
+        let mut chunk = Chunk {
+            blocks,
+            non_air_block_count,
+            ..Default::default()
+        };
+        chunk.compute_transparency();
+        Some(chunk)
+    }
+
+    /// Like [`Chunk::encode`], but with the light.
+    pub fn encode_with_light(&self, bytes: &mut Vec<u8>) {
+        self.encode(bytes);
+        bytes.push(!self.light.is_empty() as u8);
+        bytes.extend_from_slice(&self.light);
+    }
+
+    pub fn decode_with_light(bytes: &mut &[u8]) -> Option<Chunk> {
+        let mut chunk = Chunk::decode(bytes)?;
+        let (has_light, rest) = bytes.split_first()?;
+        *bytes = rest;
+        if *has_light != 0 {
+            let (light, rest) = bytes.split_at_checked(Chunk::MAX_BLOCK_COUNT as usize)?;
+            chunk.light = light.to_vec();
+            *bytes = rest;
+        }
+        Some(chunk)
+    }
+
+    pub fn index(x: usize, y: usize, z: usize) -> usize {
+        debug_assert!(x < Chunk::SIZE && y < Chunk::SIZE && z < Chunk::SIZE);
+        (x * Chunk::SIZE + y) * Chunk::SIZE + z
+    }
+
     pub fn get_transparency(&self, direction: Transparency) -> bool {
         (self.transparency & (1 << (direction as u8))) != 0
     }
 
     pub fn compute_transparency(&mut self) {
         let mut transparency = 1 << (Transparency::Computed as u8);
-        if self.non_air_block_count == Chunk::MAX_BLOCK_COUNT {
+        if self.single_block().is_some_and(|it| !it.transparent()) {
             self.transparency = transparency;
             return;
         }
@@ -43,7 +132,7 @@ impl Chunk {
             for x in (0..s).step_by(dx) {
                 for y in (0..s).step_by(dy) {
                     for z in (0..s).step_by(dz) {
-                        if self.blocks[ox + x][oy + y][oz + z].transparent() {
+                        if self.get(ox + x, oy + y, oz + z).transparent() {
                             transparency |= 1 << t as u8;
                             return;
                         }
@@ -68,35 +157,67 @@ impl Chunk {
 impl Default for Chunk {
     fn default() -> Self {
         Self {
-            blocks: Default::default(),
+            blocks: PalettedBlocks::filled(Block::AIR),
             transparency: 0,
             in_mesh_queue: false,
             non_air_block_count: 0,
+            modified: false,
+            light: Vec::new(),
+            in_light_queue: false,
+            mesh_version: 0,
         }
     }
 }
 
-#[repr(u8)]
-#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Contiguous)]
-pub enum Block {
-    #[default]
-    Air,
-    Dirt,
-    Stone,
-    Button,
-    Water,
-    Sand,
-}
+/// An id of the [`BlockRegistry`], see `blocks.txt`.
+#[repr(transparent)]
+#[derive(Default, Eq, PartialEq, Copy, Clone, Hash, Zeroable, Pod)]
+pub struct Block(u8);
 
 impl Block {
+    pub const AIR: Block = Block(0);
+
+    pub fn from_id(id: u8) -> Self {
+        Self(id)
+    }
+
+    pub fn id(self) -> u8 {
+        self.0
+    }
+
+    /// Panics if the block isn't registered
+    pub fn named(name: &str) -> Self {
+        BlockRegistry::get()
+            .by_name(name)
+            .unwrap_or_else(|| panic!("unknown block {name}"))
+    }
+
+    pub fn properties(self) -> &'static BlockProperties {
+        BlockRegistry::get().properties(self)
+    }
+
+    pub fn solid(&self) -> bool {
+        self.properties().solid
+    }
+
     pub fn transparent(&self) -> bool {
-        match self {
-            Block::Air => true,
-            Block::Dirt => false,
-            Block::Stone => false,
-            Block::Button => false,
-            Block::Water => false,
-            Block::Sand => false,
-        }
+        self.properties().transparent
+    }
+
+    pub fn fluid_level(&self) -> Option<u8> {
+        self.properties().fluid_level
+    }
+
+    /// Whether a face of this block next to `neighbour` can be seen.
+    pub fn face_visible(&self, neighbour: &Block) -> bool {
+        // water looks like one volume
+        neighbour.transparent()
+            && !(self.fluid_level().is_some() && neighbour.fluid_level().is_some())
+    }
+}
+
+impl Debug for Block {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Block({})", self.properties().name)
     }
 }
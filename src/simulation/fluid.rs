@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+
+use glam::IVec3;
+
+use crate::simulation::chunk::Block;
+use crate::simulation::position::BlockPosition;
+use crate::simulation::registry::BlockRegistry;
+use crate::simulation::world::World;
+
+/// Ticks until water reacts to a change next to it, so it moves by one block per `DELAY` ticks.
+pub const DELAY: u64 = 5;
+
+const HORIZONTAL: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// Updates the water at the given positions. The new blocks are computed first and then placed,
+/// so the result doesn't depend on the order.
+pub fn update(world: &mut World, positions: HashSet<BlockPosition>) {
+    let changes = positions
+        .into_iter()
+        .filter_map(|position| Some((position, next_block(world, position)?)))
+        .collect::<Vec<_>>();
+
+    for (position, block) in changes {
+        world.set_block(position, block);
+    }
+}
+
+/// Returns the block that should replace the one at `position`, if any.
+///
+/// Sources never change. Water below other water has level 1. Otherwise the level is one more
+/// than that of the lowest horizontal neighbour that can't flow downwards. Air between two
+/// sources on top of something becomes a source, so that holes in the sea are filled up.
+fn next_block(world: &World, position: BlockPosition) -> Option<Block> {
+    let registry = BlockRegistry::get();
+    let block = world.get_block(position)?;
+    match block.fluid_level() {
+        Some(0) => return None,
+        None if block != Block::AIR => return None,
+        _ => {}
+    }
+
+    let fluid_level = |position: BlockPosition| world.get_block(position)?.fluid_level();
+    // water doesn't flow sideways on top of air or flowing water
+    let supported = |position: BlockPosition| {
+        world
+            .get_block(position.plus(IVec3::NEG_Y))
+            .is_none_or(|it| it.fluid_level() == Some(0) || it.properties().collision)
+    };
+
+    let sources = HORIZONTAL
+        .iter()
+        .filter(|it| fluid_level(position.plus(**it)) == Some(0))
+        .count();
+
+    let level = if sources >= 2 && supported(position) {
+        Some(0)
+    } else if fluid_level(position.plus(IVec3::Y)).is_some() {
+        Some(1)
+    } else {
+        HORIZONTAL
+            .iter()
+            .map(|it| position.plus(*it))
+            .filter(|it| supported(*it))
+            .filter_map(|it| Some(fluid_level(it)? + 1))
+            .min()
+            .filter(|it| *it <= registry.max_fluid_level())
+    };
+
+    let next = level.map_or(Block::AIR, |it| registry.fluid(it).unwrap());
+    (next != block).then_some(next)
+}
+
+#[cfg(test)]
+#[test]
+fn test_water() {
+    use crate::simulation::world::at;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    let mut world = World::test_world(None);
+    world.fill_floor(Block::named("stone"));
+
+    let mut random = StdRng::seed_from_u64(0);
+    let mut run = |world: &mut World, steps: u64| {
+        world.process_neighbour_updates();
+        for _ in 0..steps * DELAY {
+            world.tick(&mut random);
+        }
+    };
+
+    let level = |world: &World, x, y, z| world.get_block(at(x, y, z)).unwrap().fluid_level();
+    let water = Block::named("water");
+    world.set_block(at(8, 3, 8), water);
+    run(&mut world, 20);
+
+    // falls down and spreads on the floor
+    assert_eq!(level(&world, 8, 2, 8), Some(1));
+    assert_eq!(level(&world, 8, 1, 8), Some(1));
+    assert_eq!(level(&world, 10, 1, 8), Some(3));
+    assert_eq!(level(&world, 9, 2, 8), None);
+    let max = BlockRegistry::get().max_fluid_level() as i32;
+    assert_eq!(level(&world, 8 + max - 1, 1, 8), Some(max as u8));
+    assert_eq!(level(&world, 8 + max, 1, 8), None);
+
+    // dries up without a source
+    world.set_block(at(8, 3, 8), Block::AIR);
+    run(&mut world, 20);
+    assert_eq!(level(&world, 8, 1, 8), None);
+
+    // a hole between two sources is filled with a new source
+    world.set_block(at(4, 1, 4), water);
+    world.set_block(at(6, 1, 4), water);
+    run(&mut world, 3);
+    assert_eq!(level(&world, 5, 1, 4), Some(0));
+}
@@ -0,0 +1,62 @@
+use glam::IVec3;
+use rand::RngExt;
+use rand::rngs::StdRng;
+
+use crate::simulation::chunk::Block;
+use crate::simulation::position::BlockPosition;
+use crate::simulation::world::World;
+
+/// Grass turns into dirt below blocks that aren't transparent. Otherwise it spreads onto a random
+/// dirt block at most one block away that has nothing on top of it.
+pub fn random_tick(world: &mut World, position: BlockPosition, random: &mut StdRng) {
+    let grass = Block::named("grass");
+    let dirt = Block::named("dirt");
+    if world.get_block(position) != Some(grass) {
+        return;
+    }
+
+    if !uncovered(world, position) {
+        world.set_block(position, dirt);
+        return;
+    }
+
+    let offset = IVec3::from_array([(); 3].map(|_| random.random_range(-1..=1)));
+    let target = position.plus(offset);
+    if world.get_block(target) == Some(dirt) && uncovered(world, target) {
+        world.set_block(target, grass);
+    }
+}
+
+fn uncovered(world: &World, position: BlockPosition) -> bool {
+    world
+        .get_block(position.plus(IVec3::Y))
+        .is_some_and(|it| it.transparent())
+}
+
+#[cfg(test)]
+#[test]
+fn test_grass() {
+    use rand::SeedableRng;
+
+    let mut world = World::test_world(None);
+    let dirt = Block::named("dirt");
+    world.fill_floor(dirt);
+
+    let at = |x, z| BlockPosition::new(IVec3::new(x, 0, z));
+    let grass = Block::named("grass");
+    world.set_block(at(8, 8), grass);
+    world.set_block(at(9, 8).plus(IVec3::Y), Block::named("stone"));
+
+    let mut random = StdRng::seed_from_u64(0);
+    for _ in 0..200 {
+        random_tick(&mut world, at(8, 8), &mut random);
+    }
+    assert_eq!(world.get_block(at(7, 8)), Some(grass));
+    assert_eq!(world.get_block(at(8, 9)), Some(grass));
+    assert_eq!(world.get_block(at(9, 8)), Some(dirt));
+    assert_eq!(world.get_block(at(10, 8)), Some(dirt));
+
+    world.set_block(at(8, 8).plus(IVec3::Y), Block::named("stone"));
+    random_tick(&mut world, at(8, 8), &mut random);
+    assert_eq!(world.get_block(at(8, 8)), Some(dirt));
+}
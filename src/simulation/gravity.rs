@@ -0,0 +1,61 @@
+use glam::IVec3;
+
+use crate::simulation::chunk::Block;
+use crate::simulation::position::BlockPosition;
+use crate::simulation::world::World;
+
+/// Moves a falling block at `position` down until it lands on a block with collision. Air and
+/// fluids are replaced. Chunks that aren't loaded stop the fall.
+pub fn update(world: &mut World, position: BlockPosition) {
+    let Some(block) = world.get_block(position) else {
+        return;
+    };
+    if !block.properties().falls {
+        return;
+    }
+
+    let mut landing = position;
+    while world
+        .get_block(landing.plus(IVec3::NEG_Y))
+        .is_some_and(|it| !it.properties().collision)
+    {
+        landing = landing.plus(IVec3::NEG_Y);
+    }
+
+    if landing != position {
+        world.set_block(position, Block::AIR);
+        world.set_block(landing, block);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_falling_blocks() {
+    use crate::simulation::position::ChunkPosition;
+    use crate::simulation::world::at;
+
+    let mut world = World::test_world(None);
+    let stone = Block::named("stone");
+    world.fill_floor(stone);
+    world.add_air_chunk(ChunkPosition::from_chunk_index(IVec3::Y));
+
+    let sand = Block::named("sand");
+    world.set_block(at(4, 1, 4), stone);
+    world.set_block(at(4, 2, 4), sand);
+    world.set_block(at(4, 3, 4), sand);
+    world.process_neighbour_updates();
+    assert_eq!(world.get_block(at(4, 3, 4)), Some(sand));
+
+    // the whole column falls when the support is removed
+    world.set_block(at(4, 1, 4), Block::AIR);
+    world.process_neighbour_updates();
+    assert_eq!(world.get_block(at(4, 1, 4)), Some(sand));
+    assert_eq!(world.get_block(at(4, 2, 4)), Some(sand));
+    assert_eq!(world.get_block(at(4, 3, 4)), Some(Block::AIR));
+
+    // falls into the chunk below
+    world.set_block(at(8, 20, 8), sand);
+    world.process_neighbour_updates();
+    assert_eq!(world.get_block(at(8, 20, 8)), Some(Block::AIR));
+    assert_eq!(world.get_block(at(8, 1, 8)), Some(sand));
+}
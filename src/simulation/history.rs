@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+
+use crate::simulation::chunk::Block;
+use crate::simulation::position::BlockPosition;
+
+/// Oldest batches are dropped when the history contains more edits. A batch of an explosion
+/// with diameter 20 contains about 4000 edits.
+const MAX_EDITS: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edit {
+    pub position: BlockPosition,
+    pub previous: Block,
+    pub new: Block,
+}
+
+/// Block changes grouped into batches, one for each player command. Edits are stored by world
+/// position, so they can be replayed after their chunks were unloaded and loaded again.
+#[derive(Default)]
+pub struct EditHistory {
+    undo: VecDeque<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    edit_count: usize,
+    /// The batch that is recorded right now
+    current: Option<Vec<Edit>>,
+}
+
+impl EditHistory {
+    pub fn begin(&mut self) {
+        debug_assert!(self.current.is_none());
+        self.current = Some(Vec::new());
+    }
+
+    pub fn record(&mut self, edit: Edit) {
+        if let Some(current) = &mut self.current {
+            current.push(edit);
+        }
+    }
+
+    /// Finishes the current batch. New edits make the undone batches unreachable.
+    pub fn end(&mut self) {
+        let Some(batch) = self.current.take() else {
+            return;
+        };
+        if batch.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.push_undo(batch);
+    }
+
+    /// The batch that [`EditHistory::undo`] would return, in the order in which it was recorded
+    pub fn next_undo(&self) -> Option<&[Edit]> {
+        self.undo.back().map(Vec::as_slice)
+    }
+
+    /// The batch that [`EditHistory::redo`] would return
+    pub fn next_redo(&self) -> Option<&[Edit]> {
+        self.redo.last().map(Vec::as_slice)
+    }
+
+    /// Returns the edits of the last batch in the order in which they have to be reverted.
+    pub fn undo(&mut self) -> Option<Vec<Edit>> {
+        let mut batch = self.undo.pop_back()?;
+        self.edit_count -= batch.len();
+        self.redo.push(batch.clone());
+        batch.reverse();
+        Some(batch)
+    }
+
+    /// Returns the edits of the last undone batch in the order in which they have to be applied.
+    pub fn redo(&mut self) -> Option<Vec<Edit>> {
+        let batch = self.redo.pop()?;
+        self.push_undo(batch.clone());
+        Some(batch)
+    }
+
+    fn push_undo(&mut self, batch: Vec<Edit>) {
+        self.edit_count += batch.len();
+        self.undo.push_back(batch);
+        while self.edit_count > MAX_EDITS && self.undo.len() > 1 {
+            let oldest = self.undo.pop_front().unwrap();
+            self.edit_count -= oldest.len();
+        }
+    }
+
+    /// Edits are only recorded between [`EditHistory::begin`] and [`EditHistory::end`].
+    pub fn recording(&self) -> bool {
+        self.current.is_some()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_undo() {
+    use crate::simulation::position::ChunkPosition;
+    use crate::simulation::region::RegionStorage;
+    use crate::simulation::world::World;
+    use glam::IVec3;
+
+    let directory = std::env::temp_dir().join(format!("history-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let storage = RegionStorage::open(&directory).unwrap();
+    let mut world = World::test_world(Some(storage));
+    let origin = ChunkPosition::from_chunk_index(IVec3::ZERO);
+
+    let at = |x| BlockPosition::new(IVec3::new(x, 0, 0));
+    let stone = Block::named("stone");
+    let dirt = Block::named("dirt");
+    world.begin_edit();
+    world.set_block(at(1), stone);
+    world.set_block(at(2), stone);
+    world.end_edit();
+    world.begin_edit();
+    world.set_block(at(1), dirt);
+    world.end_edit();
+    // not recorded
+    world.set_block(at(3), stone);
+
+    // the chunk is unloaded and loaded again in between, the batch waits for it
+    world.crop(origin.plus(IVec3::X * 5));
+    assert!(world.get_chunk(origin).is_none());
+    assert!(world.undo().is_err());
+    let chunk = world.load_stored_chunk(origin).unwrap();
+    world.add_chunk(origin, chunk);
+
+    assert_eq!(world.undo(), Ok(()));
+    assert_eq!(world.get_block(at(1)), Some(stone));
+    assert_eq!(world.undo(), Ok(()));
+    assert_eq!(world.get_block(at(1)), Some(Block::AIR));
+    assert_eq!(world.get_block(at(2)), Some(Block::AIR));
+    assert_eq!(world.get_block(at(3)), Some(stone));
+    assert!(world.undo().is_err());
+
+    assert_eq!(world.redo(), Ok(()));
+    assert_eq!(world.get_block(at(2)), Some(stone));
+    // a new edit drops the batch that could be redone
+    world.begin_edit();
+    world.set_block(at(4), stone);
+    world.end_edit();
+    assert!(world.redo().is_err());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
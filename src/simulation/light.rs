@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+
+use glam::IVec3;
+
+use crate::simulation::chunk::Chunk;
+use crate::simulation::world::DIRECTIONS;
+
+/// Light levels go from 0 to `MAX`. Each byte stores the sky light in the upper 4 bits and the
+/// light of emissive blocks in the lower 4 bits.
+pub const MAX: u8 = 15;
+pub const FULL_SKY: u8 = MAX << 4;
+
+const S: i32 = Chunk::SIZE as i32;
+const LEN: usize = Chunk::SIZE * Chunk::SIZE * Chunk::SIZE;
+
+pub fn sky(light: u8) -> u8 {
+    light >> 4
+}
+
+pub fn block(light: u8) -> u8 {
+    light & MAX
+}
+
+fn pack(sky: u8, block: u8) -> u8 {
+    (sky << 4) | block
+}
+
+fn index(p: IVec3) -> usize {
+    Chunk::index(p.x as usize, p.y as usize, p.z as usize)
+}
+
+fn inside(p: IVec3) -> bool {
+    p.cmpge(IVec3::ZERO).all() && p.cmplt(IVec3::splat(S)).all()
+}
+
+/// Computes the light of every block in the chunk from its blocks and the light at the borders
+/// of the neighbours, in the order of [`DIRECTIONS`].
+///
+/// Sky light keeps its full strength while it goes straight down through transparent blocks,
+/// if the chunk above isn't loaded it counts as open sky. Apart from that, light gets weaker by
+/// one per block and doesn't pass blocks that aren't transparent.
+pub fn compute(chunk: &Chunk, neighbours: &[Option<&Chunk>; 6]) -> Vec<u8> {
+    let mut transparent = vec![false; LEN];
+    let mut sky_light = vec![0u8; LEN];
+    let mut block_light = vec![0u8; LEN];
+    let mut queue = VecDeque::new();
+
+    for x in 0..S {
+        for y in 0..S {
+            for z in 0..S {
+                let p = IVec3::new(x, y, z);
+                let block = chunk.get(x as usize, y as usize, z as usize);
+                transparent[index(p)] = block.transparent();
+                let emission = block.properties().light.min(MAX);
+                if emission > 0 {
+                    block_light[index(p)] = emission;
+                    queue.push_back(p);
+                }
+            }
+        }
+    }
+
+    // direct sky light
+    for x in 0..S {
+        for z in 0..S {
+            let from_above =
+                neighbours[2].map_or(MAX, |it| sky(it.light(x as usize, 0, z as usize)));
+            if from_above < MAX {
+                continue;
+            }
+            for y in (0..S).rev() {
+                let p = IVec3::new(x, y, z);
+                if !transparent[index(p)] {
+                    break;
+                }
+                sky_light[index(p)] = MAX;
+                queue.push_back(p);
+            }
+        }
+    }
+
+    // light from the neighbours
+    for (direction, neighbour) in DIRECTIONS.iter().zip(neighbours) {
+        let Some(neighbour) = neighbour else {
+            continue;
+        };
+        for p in border(*direction) {
+            if !transparent[index(p)] {
+                continue;
+            }
+            let n = p + *direction - *direction * S;
+            let light = neighbour.light(n.x as usize, n.y as usize, n.z as usize);
+            let i = index(p);
+            sky_light[i] = sky_light[i].max(sky(light).saturating_sub(1));
+            block_light[i] = block_light[i].max(block(light).saturating_sub(1));
+            queue.push_back(p);
+        }
+    }
+
+    while let Some(p) = queue.pop_front() {
+        let sky = sky_light[index(p)].saturating_sub(1);
+        let block = block_light[index(p)].saturating_sub(1);
+        if sky == 0 && block == 0 {
+            continue;
+        }
+        for direction in DIRECTIONS {
+            let n = p + direction;
+            if !inside(n) || !transparent[index(n)] {
+                continue;
+            }
+            let i = index(n);
+            if sky > sky_light[i] || block > block_light[i] {
+                sky_light[i] = sky_light[i].max(sky);
+                block_light[i] = block_light[i].max(block);
+                queue.push_back(n);
+            }
+        }
+    }
+
+    sky_light
+        .into_iter()
+        .zip(block_light)
+        .map(|(sky, block)| pack(sky, block))
+        .collect()
+}
+
+/// Whether sky light reaches the whole bottom of `above`, so that air below it has full sky
+/// light. Chunks that aren't loaded count as open sky, like in [`compute`].
+pub fn open_sky_below(above: Option<&Chunk>) -> bool {
+    above.is_none_or(|it| {
+        (0..Chunk::SIZE).all(|x| (0..Chunk::SIZE).all(|z| sky(it.light(x, 0, z)) == MAX))
+    })
+}
+
+/// Whether the light of the blocks next to the neighbour in `direction` is different.
+pub fn border_changed(old: &Chunk, new: &[u8], direction: IVec3) -> bool {
+    border(direction).any(|p| old.light(p.x as usize, p.y as usize, p.z as usize) != new[index(p)])
+}
+
+/// Positions of the blocks next to the neighbour in `direction`
+fn border(direction: IVec3) -> impl Iterator<Item = IVec3> {
+    let axis = direction.abs().max_position();
+    let value = if direction[axis] > 0 { S - 1 } else { 0 };
+    (0..S).flat_map(move |a| {
+        (0..S).map(move |b| {
+            let mut p = IVec3::ZERO;
+            p[axis] = value;
+            p[(axis + 1) % 3] = a;
+            p[(axis + 2) % 3] = b;
+            p
+        })
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn test_light() {
+    use crate::simulation::chunk::Block;
+
+    let stone = Block::named("stone");
+    let mut chunk = Chunk::default();
+    // a roof with a hole
+    for x in 0..Chunk::SIZE {
+        for z in 0..Chunk::SIZE {
+            if (x, z) != (2, 2) {
+                chunk.set(x, 10, z, stone);
+            }
+        }
+    }
+    let air = Chunk::default();
+    let neighbours = [None, None, None, None, None, None];
+    let light = compute(&chunk, &neighbours);
+    let at = |x, y, z| light[Chunk::index(x, y, z)];
+
+    assert_eq!(at(5, 12, 5), FULL_SKY);
+    assert_eq!(at(5, 10, 5), 0);
+    assert_eq!(sky(at(2, 3, 2)), MAX);
+    assert_eq!(sky(at(5, 3, 2)), MAX - 3);
+    assert_eq!(sky(at(15, 9, 15)), 0);
+
+    // covered by a chunk above that is dark at the bottom
+    let mut dark = Chunk::default();
+    dark.light = vec![0; LEN];
+    let light = compute(&air, &[None, None, Some(&dark), None, None, None]);
+    assert_eq!(light[Chunk::index(3, 3, 3)], 0);
+
+    // emissive blocks
+    let mut lamp = Chunk::default();
+    lamp.set(8, 8, 8, Block::named("lamp"));
+    let light = compute(&lamp, &[None, None, Some(&dark), None, None, None]);
+    assert_eq!(block(light[Chunk::index(8, 8, 8)]), MAX);
+    assert_eq!(block(light[Chunk::index(8, 8, 10)]), MAX - 2);
+    assert_eq!(sky(light[Chunk::index(8, 8, 10)]), 0);
+    assert!(border_changed(&air, &light, IVec3::X));
+}
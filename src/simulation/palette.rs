@@ -0,0 +1,219 @@
+use crate::simulation::chunk::{Block, Chunk};
+use crate::simulation::registry::BlockRegistry;
+
+const LEN: usize = Chunk::SIZE * Chunk::SIZE * Chunk::SIZE;
+
+/// The blocks of a chunk as bit packed indices into a palette.
+///
+/// Indices use 0, 1, 2, 4, 8 or 16 bits, so that they never cross the boundary of a word.
+/// Chunks that only contain a single kind of block don't store any indices.
+#[derive(Clone)]
+pub struct PalettedBlocks {
+    palette: Vec<Block>,
+    /// How many blocks use each palette entry. Entries with a count of 0 are reused.
+    counts: Vec<u16>,
+    bits: usize,
+    words: Vec<u64>,
+}
+
+impl PalettedBlocks {
+    pub fn filled(block: Block) -> Self {
+        Self {
+            palette: vec![block],
+            counts: vec![LEN as u16],
+            bits: 0,
+            words: vec![],
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Block {
+        self.palette[self.palette_index(index)]
+    }
+
+    /// Returns the previous block.
+    pub fn set(&mut self, index: usize, block: Block) -> Block {
+        let old = self.palette_index(index);
+        let previous = self.palette[old];
+        if previous == block {
+            return previous;
+        }
+
+        let new = if let Some(i) = self.palette.iter().position(|it| *it == block) {
+            i
+        } else if let Some(i) = self.counts.iter().position(|it| *it == 0) {
+            self.palette[i] = block;
+            i
+        } else {
+            self.palette.push(block);
+            self.counts.push(0);
+            let bits = bits_for(self.palette.len());
+            if bits > self.bits {
+                self.repack(bits);
+            }
+            self.palette.len() - 1
+        };
+
+        self.counts[old] -= 1;
+        self.counts[new] += 1;
+
+        if self.counts[new] as usize == LEN {
+            *self = Self::filled(block);
+        } else {
+            self.set_palette_index(index, new);
+        }
+        previous
+    }
+
+    /// Returns the block if all blocks are the same.
+    pub fn single(&self) -> Option<Block> {
+        (self.bits == 0).then_some(self.palette[0])
+    }
+
+    pub fn count(&self, block: Block) -> usize {
+        self.palette
+            .iter()
+            .zip(self.counts.iter())
+            .filter(|(b, _)| **b == block)
+            .map(|(_, c)| *c as usize)
+            .sum()
+    }
+
+    /// Layout: `palette_length: u16, bits: u8, padding: u8`, the palette as one byte per block and
+    /// padding to a multiple of 4, followed by the little endian u64 words. Unused palette
+    /// entries are removed first.
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        if self.counts.contains(&0) {
+            let mut compact = Self::filled(self.palette[self.palette_index(0)]);
+            for i in 1..LEN {
+                compact.set(i, self.get(i));
+            }
+            return compact.encode(bytes);
+        }
+
+        bytes.extend_from_slice(&(self.palette.len() as u16).to_le_bytes());
+        bytes.push(self.bits as u8);
+        bytes.push(0);
+        bytes.extend(self.palette.iter().map(|it| it.id()));
+        let padding = self.palette.len().next_multiple_of(4) - self.palette.len();
+        bytes.extend(std::iter::repeat_n(0, padding));
+        for word in self.words.iter() {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    pub fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        let (header, rest) = bytes.split_first_chunk::<4>()?;
+        let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+        let bits = header[2] as usize;
+        if length == 0 || bits != bits_for(length) {
+            return None;
+        }
+
+        let padded = length.next_multiple_of(4);
+        let word_count = LEN * bits / 64;
+        if rest.len() < padded + word_count * 8 {
+            return None;
+        }
+        let registered = BlockRegistry::get().len();
+        let palette = rest[..length]
+            .iter()
+            .map(|it| ((*it as usize) < registered).then_some(Block::from_id(*it)))
+            .collect::<Option<Vec<_>>>()?;
+        let words = rest[padded..][..word_count * 8]
+            .chunks_exact(8)
+            .map(|it| u64::from_le_bytes(it.try_into().unwrap()))
+            .collect();
+        *bytes = &rest[padded + word_count * 8..];
+
+        let mut result = Self {
+            palette,
+            counts: vec![0; length],
+            bits,
+            words,
+        };
+        if bits == 0 {
+            result.counts[0] = LEN as u16;
+        } else {
+            for i in 0..LEN {
+                let index = result.palette_index(i);
+                *result.counts.get_mut(index)? += 1;
+            }
+        }
+        Some(result)
+    }
+
+    fn palette_index(&self, index: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = 64 / self.bits;
+        let word = self.words[index / per_word];
+        let shift = (index % per_word) * self.bits;
+        ((word >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+
+    fn set_palette_index(&mut self, index: usize, value: usize) {
+        let per_word = 64 / self.bits;
+        let word = &mut self.words[index / per_word];
+        let shift = (index % per_word) * self.bits;
+        let mask = ((1u64 << self.bits) - 1) << shift;
+        *word = (*word & !mask) | ((value as u64) << shift);
+    }
+
+    fn repack(&mut self, bits: usize) {
+        let indices = (0..LEN).map(|i| self.palette_index(i)).collect::<Vec<_>>();
+        self.bits = bits;
+        self.words = vec![0; LEN * bits / 64];
+        for (i, value) in indices.into_iter().enumerate() {
+            self.set_palette_index(i, value);
+        }
+    }
+}
+
+fn bits_for(palette_length: usize) -> usize {
+    match palette_length {
+        0..=1 => 0,
+        2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        17..=256 => 8,
+        _ => 16,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_palette() {
+    let [stone, dirt, sand, water] = ["stone", "dirt", "sand", "water"].map(Block::named);
+
+    let mut blocks = PalettedBlocks::filled(Block::AIR);
+    assert!(blocks.words.is_empty());
+
+    assert_eq!(blocks.set(7, stone), Block::AIR);
+    assert_eq!(blocks.set(LEN - 1, dirt), Block::AIR);
+    assert_eq!(blocks.bits, 2);
+    assert_eq!(blocks.get(7), stone);
+    assert_eq!(blocks.get(LEN - 1), dirt);
+    assert_eq!(blocks.get(8), Block::AIR);
+    assert_eq!(blocks.count(Block::AIR), LEN - 2);
+
+    // the entry of stone is reused
+    assert_eq!(blocks.set(7, Block::AIR), stone);
+    blocks.set(8, sand);
+    assert_eq!(blocks.palette.len(), 3);
+
+    let mut bytes = vec![];
+    blocks.encode(&mut bytes);
+    assert_eq!(bytes.len() % 4, 0);
+    let mut remainder = &bytes[..];
+    let decoded = PalettedBlocks::decode(&mut remainder).unwrap();
+    assert!(remainder.is_empty());
+    assert!((0..LEN).all(|i| decoded.get(i) == blocks.get(i)));
+
+    // collapses to a single value
+    for i in 0..LEN {
+        blocks.set(i, water);
+    }
+    assert_eq!(blocks.single(), Some(water));
+    assert!(blocks.words.is_empty());
+}
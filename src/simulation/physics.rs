@@ -0,0 +1,221 @@
+use glam::{IVec3, Vec3};
+
+use crate::simulation::position::ChunkPosition;
+use crate::simulation::world::World;
+
+/// Gaps smaller than this are treated as touching, so that boxes resting on a face don't
+/// collide with the blocks on the other side of it.
+const EPSILON: f32 = 1e-4;
+
+/// Axis aligned box relative to a chunk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    #[must_use]
+    pub fn translated(self, offset: Vec3) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PlayerBody {
+    pub velocity: Vec3,
+    pub on_ground: bool,
+    pub flying: bool,
+}
+
+impl PlayerBody {
+    const HALF_WIDTH: f32 = 0.3;
+    const HEIGHT: f32 = 1.8;
+    /// Distance from the feet to the camera
+    const EYE_HEIGHT: f32 = 1.6;
+
+    const GRAVITY: f32 = 28.0;
+    const TERMINAL_VELOCITY: f32 = 60.0;
+    const JUMP_SPEED: f32 = 9.0;
+    const WALK_SPEED: f32 = 5.0;
+    const FLY_SPEED: f32 = 100.0;
+    const STEP_HEIGHT: f32 = 1.0;
+
+    pub fn bounds(eye: Vec3) -> Aabb {
+        Aabb {
+            min: eye - Vec3::new(Self::HALF_WIDTH, Self::EYE_HEIGHT, Self::HALF_WIDTH),
+            max: eye
+                + Vec3::new(
+                    Self::HALF_WIDTH,
+                    Self::HEIGHT - Self::EYE_HEIGHT,
+                    Self::HALF_WIDTH,
+                ),
+        }
+    }
+
+    /// Moves the body according to the input direction and returns the new eye position.
+    ///
+    /// While walking only the horizontal part of `input` is used and its length is capped at 1.
+    pub fn update(
+        &mut self,
+        world: &World,
+        chunk: ChunkPosition,
+        eye: Vec3,
+        input: Vec3,
+        jump: bool,
+        delta_time: f32,
+    ) -> Vec3 {
+        let bounds = Self::bounds(eye);
+
+        if self.flying {
+            self.velocity = Vec3::ZERO;
+            self.on_ground = false;
+            let delta = input * Self::FLY_SPEED * delta_time;
+            let moved = sweep_axis(world, chunk, bounds, 1, delta.y);
+            let bounds = bounds.translated(Vec3::Y * moved);
+            return eye + Vec3::Y * moved + move_horizontally(world, chunk, bounds, delta);
+        }
+
+        let walk = Vec3::new(input.x, 0.0, input.z).normalize_or_zero() * input.length().min(1.0);
+        if jump && self.on_ground {
+            self.velocity.y = Self::JUMP_SPEED;
+        }
+        self.velocity.y =
+            (self.velocity.y - Self::GRAVITY * delta_time).max(-Self::TERMINAL_VELOCITY);
+
+        let delta = walk * Self::WALK_SPEED * delta_time + Vec3::Y * self.velocity.y * delta_time;
+
+        let fall = sweep_axis(world, chunk, bounds, 1, delta.y);
+        self.on_ground = delta.y < 0.0 && fall > delta.y;
+        if fall != delta.y {
+            self.velocity.y = 0.0;
+        }
+        let bounds = bounds.translated(Vec3::Y * fall);
+
+        let mut moved = move_horizontally(world, chunk, bounds, delta);
+        if self.on_ground && (moved.x != delta.x || moved.z != delta.z) {
+            // try again from one block higher and keep it if that gets us further
+            let up = sweep_axis(world, chunk, bounds, 1, Self::STEP_HEIGHT);
+            let raised = bounds.translated(Vec3::Y * up);
+            let stepped = move_horizontally(world, chunk, raised, delta);
+            if stepped.length_squared() > moved.length_squared() {
+                let down = sweep_axis(world, chunk, raised.translated(stepped), 1, -up);
+                moved = stepped + Vec3::Y * (up + down);
+            }
+        }
+
+        eye + Vec3::Y * fall + moved
+    }
+}
+
+/// Moves the box along x and then along z.
+fn move_horizontally(world: &World, chunk: ChunkPosition, bounds: Aabb, delta: Vec3) -> Vec3 {
+    let x = sweep_axis(world, chunk, bounds, 0, delta.x);
+    let bounds = bounds.translated(Vec3::X * x);
+    let z = sweep_axis(world, chunk, bounds, 2, delta.z);
+    Vec3::new(x, 0.0, z)
+}
+
+/// Returns how far the box can move along the axis before it touches a block with collision.
+/// Blocks in chunks that aren't loaded yet are solid. Blocks that already intersect the box are
+/// ignored, so that it is always possible to get out of them.
+pub fn sweep_axis(
+    world: &World,
+    chunk: ChunkPosition,
+    bounds: Aabb,
+    axis: usize,
+    distance: f32,
+) -> f32 {
+    if distance == 0.0 {
+        return 0.0;
+    }
+
+    let mut swept = bounds;
+    if distance > 0.0 {
+        swept.max[axis] += distance;
+    } else {
+        swept.min[axis] += distance;
+    }
+    let low = (swept.min + EPSILON).floor().as_ivec3();
+    let high = (swept.max - EPSILON).floor().as_ivec3();
+
+    let origin = chunk.block();
+    let mut allowed = distance;
+    for x in low.x..=high.x {
+        for y in low.y..=high.y {
+            for z in low.z..=high.z {
+                let cell = IVec3::new(x, y, z);
+                let collision = world
+                    .get_block(origin.plus(cell))
+                    .is_none_or(|it| it.properties().collision);
+                if !collision {
+                    continue;
+                }
+
+                if distance > 0.0 {
+                    let gap = cell[axis] as f32 - bounds.max[axis];
+                    if gap >= -EPSILON {
+                        allowed = allowed.min(gap.max(0.0));
+                    }
+                } else {
+                    let gap = cell[axis] as f32 + 1.0 - bounds.min[axis];
+                    if gap <= EPSILON {
+                        allowed = allowed.max(gap.min(0.0));
+                    }
+                }
+            }
+        }
+    }
+    allowed
+}
+
+#[cfg(test)]
+#[test]
+fn test_player_body() {
+    use crate::simulation::chunk::{Block, Chunk};
+
+    let origin = ChunkPosition::from_chunk_index(IVec3::ZERO);
+    let mut world = World::test_world(None);
+    let stone = Block::named("stone");
+    world.fill_floor(stone);
+    // a ledge
+    world.fill(IVec3::new(8, 1, 0), IVec3::new(15, 1, 15), stone);
+
+    let mut body = PlayerBody::default();
+    let mut eye = Vec3::new(4.5, 4.0, 4.5);
+    let step = |body: &mut PlayerBody, eye: Vec3, input: Vec3, jump: bool| {
+        body.update(&world, origin, eye, input, jump, 1.0 / 60.0)
+    };
+
+    // falls onto the floor
+    for _ in 0..60 {
+        eye = step(&mut body, eye, Vec3::ZERO, false);
+    }
+    assert!(body.on_ground);
+    assert!((eye.y - (1.0 + PlayerBody::EYE_HEIGHT)).abs() < 0.01);
+
+    // jumps
+    eye = step(&mut body, eye, Vec3::ZERO, true);
+    assert!(!body.on_ground);
+    assert!(body.velocity.y > 0.0);
+    for _ in 0..60 {
+        eye = step(&mut body, eye, Vec3::ZERO, false);
+    }
+    assert!(body.on_ground);
+
+    // steps onto the ledge, looking down doesn't slow us down
+    for _ in 0..60 {
+        eye = step(&mut body, eye, Vec3::new(1.0, -1.0, 0.0).normalize(), false);
+    }
+    assert!(eye.x > 9.0);
+    assert!((eye.y - (2.0 + PlayerBody::EYE_HEIGHT)).abs() < 0.01);
+
+    // walls of unloaded chunks stop the player
+    for _ in 0..120 {
+        eye = step(&mut body, eye, Vec3::X, false);
+    }
+    assert!((eye.x - (Chunk::SIZE as f32 - PlayerBody::HALF_WIDTH)).abs() < 0.01);
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use bytemuck::{Pod, Zeroable};
+use glam::IVec3;
+
+use crate::simulation::chunk::Chunk;
+
+/// Chunk columns are grouped into files of `REGION_SIZE` x `REGION_SIZE` columns.
+///
+/// Layout of a region file:
+/// - sectors 0 and 1: offset table with one `(first_sector: u32, byte_length: u32)` entry per
+///   column, indexed by `x + z * REGION_SIZE`. A length of 0 means the column was never stored.
+/// - all following sectors: column entries, each starting at a sector boundary.
+///
+/// A column entry is `format: u32, count: u32` followed by `count` chunks that are each stored
+/// as `y: i32, length: u32` and `length` bytes of [`Chunk::encode`].
+///
+/// All integers are little endian.
+pub const REGION_SIZE: i32 = 32;
+const SECTOR_SIZE: u64 = 4096;
+const HEADER_SECTORS: usize = 2;
+const COLUMN_FORMAT: u32 = 2;
+
+pub struct RegionStorage {
+    directory: PathBuf,
+    regions: HashMap<(i32, i32), RegionFile>,
+}
+
+/// Everything apart from the chunks that is needed to resume a session.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Zeroable, Pod)]
+pub struct LevelData {
+    pub seed: u64,
+    pub player_chunk: [i32; 3],
+    pub player_position: [f32; 3],
+}
+
+impl RegionStorage {
+    pub fn open(directory: impl AsRef<Path>) -> io::Result<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            regions: HashMap::new(),
+        })
+    }
+
+    pub fn load_level(&self) -> io::Result<Option<LevelData>> {
+        match fs::read(self.directory.join("level.dat")) {
+            Ok(bytes) => Ok(bytemuck::try_pod_read_unaligned(&bytes).ok()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn store_level(&self, level: &LevelData) -> io::Result<()> {
+        fs::write(self.directory.join("level.dat"), bytemuck::bytes_of(level))
+    }
+
+    /// Returns all chunks that were stored for the column `(x, z)`.
+    pub fn load_column(&mut self, x: i32, z: i32) -> io::Result<Vec<(i32, Chunk)>> {
+        let (region, index) = self.region(x, z)?;
+        let Some(bytes) = region.read(index)? else {
+            return Ok(vec![]);
+        };
+        let chunks = column_entries(&bytes).and_then(|entries| {
+            (entries.into_iter())
+                .map(|(y, mut encoded)| Some((y, Chunk::decode(&mut encoded)?)))
+                .collect()
+        });
+        chunks.ok_or_else(|| self.corrupt(x, z))
+    }
+
+    /// Only decodes the requested chunk of its column.
+    pub fn load_chunk(&mut self, position: IVec3) -> io::Result<Option<Chunk>> {
+        let (region, index) = self.region(position.x, position.z)?;
+        let Some(bytes) = region.read(index)? else {
+            return Ok(None);
+        };
+        let entries = column_entries(&bytes).ok_or_else(|| self.corrupt(position.x, position.z))?;
+        let Some((_, mut encoded)) = entries.into_iter().find(|(y, _)| *y == position.y) else {
+            return Ok(None);
+        };
+        let chunk =
+            Chunk::decode(&mut encoded).ok_or_else(|| self.corrupt(position.x, position.z))?;
+        Ok(Some(chunk))
+    }
+
+    fn corrupt(&self, x: i32, z: i32) -> io::Error {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("corrupt column ({x}, {z}) in {:?}", self.directory),
+        )
+    }
+
+    /// Adds or replaces chunks of the column `(x, z)`. Chunks at other heights that were stored
+    /// earlier are kept.
+    pub fn store_column(&mut self, x: i32, z: i32, chunks: &[(i32, &Chunk)]) -> io::Result<()> {
+        let mut stored = self.load_column(x, z)?;
+        stored.retain(|(y, _)| chunks.iter().all(|(new_y, _)| new_y != y));
+
+        let mut all = chunks.to_vec();
+        all.extend(stored.iter().map(|(y, chunk)| (*y, chunk)));
+        all.sort_by_key(|(y, _)| *y);
+
+        let bytes = encode_column(&all);
+        let (region, index) = self.region(x, z)?;
+        region.write(index, &bytes)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for region in self.regions.values_mut() {
+            region.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn region(&mut self, x: i32, z: i32) -> io::Result<(&mut RegionFile, usize)> {
+        let key = (x.div_euclid(REGION_SIZE), z.div_euclid(REGION_SIZE));
+        let index = (x.rem_euclid(REGION_SIZE) + z.rem_euclid(REGION_SIZE) * REGION_SIZE) as usize;
+
+        let region = match self.regions.entry(key) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let path = self.directory.join(format!("r.{}.{}.region", key.0, key.1));
+                e.insert(RegionFile::open(&path)?)
+            }
+        };
+        Ok((region, index))
+    }
+}
+
+struct RegionFile {
+    file: File,
+    table: Vec<(u32, u32)>,
+    used_sectors: Vec<bool>,
+}
+
+impl RegionFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let column_count = (REGION_SIZE * REGION_SIZE) as usize;
+        let header_size = HEADER_SECTORS * SECTOR_SIZE as usize;
+        debug_assert_eq!(column_count * 8, header_size);
+
+        let mut header = vec![0u8; header_size];
+        if file.metadata()?.len() < header_size as u64 {
+            file.write_all(&header)?;
+        } else {
+            file.read_exact(&mut header)?;
+        }
+
+        let table = header
+            .chunks_exact(8)
+            .map(|entry| {
+                (
+                    u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+                    u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut used_sectors = vec![true; HEADER_SECTORS];
+        for &(sector, length) in table.iter().filter(|(_, length)| *length != 0) {
+            let range = sector as usize..sector as usize + sector_count(length);
+            if used_sectors.len() < range.end {
+                used_sectors.resize(range.end, false);
+            }
+            used_sectors[range].fill(true);
+        }
+
+        Ok(Self {
+            file,
+            table,
+            used_sectors,
+        })
+    }
+
+    fn read(&mut self, index: usize) -> io::Result<Option<Vec<u8>>> {
+        let (sector, length) = self.table[index];
+        if length == 0 {
+            return Ok(None);
+        }
+        let mut bytes = vec![0u8; length as usize];
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.read_exact(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    fn write(&mut self, index: usize, bytes: &[u8]) -> io::Result<()> {
+        let (old_sector, old_length) = self.table[index];
+        if old_length != 0 {
+            let old = old_sector as usize..old_sector as usize + sector_count(old_length);
+            self.used_sectors[old].fill(false);
+        }
+
+        // first fit, the previous location of this column is also a candidate
+        let count = sector_count(bytes.len() as u32);
+        let mut sector = HEADER_SECTORS;
+        while sector < self.used_sectors.len() {
+            let end = (sector + count).min(self.used_sectors.len());
+            match self.used_sectors[sector..end].iter().position(|it| *it) {
+                Some(used) => sector += used + 1,
+                None => break,
+            }
+        }
+        if self.used_sectors.len() < sector + count {
+            self.used_sectors.resize(sector + count, false);
+        }
+        self.used_sectors[sector..sector + count].fill(true);
+
+        let mut padded = bytes.to_vec();
+        padded.resize(count * SECTOR_SIZE as usize, 0);
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.write_all(&padded)?;
+
+        let entry = (sector as u32, bytes.len() as u32);
+        self.table[index] = entry;
+        self.file.seek(SeekFrom::Start(index as u64 * 8))?;
+        self.file.write_all(&entry.0.to_le_bytes())?;
+        self.file.write_all(&entry.1.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+fn sector_count(length: u32) -> usize {
+    (length as u64).div_ceil(SECTOR_SIZE) as usize
+}
+
+fn encode_column(chunks: &[(i32, &Chunk)]) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&COLUMN_FORMAT.to_le_bytes());
+    bytes.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    for (y, chunk) in chunks {
+        let mut encoded = vec![];
+        chunk.encode(&mut encoded);
+        bytes.extend_from_slice(&y.to_le_bytes());
+        bytes.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&encoded);
+    }
+    bytes
+}
+
+/// The heights and encoded chunks of a column entry, so that single chunks can be decoded
+fn column_entries(mut bytes: &[u8]) -> Option<Vec<(i32, &[u8])>> {
+    let take_u32 = |bytes: &mut &[u8]| -> Option<u32> {
+        let (value, rest) = bytes.split_first_chunk::<4>()?;
+        *bytes = rest;
+        Some(u32::from_le_bytes(*value))
+    };
+
+    if take_u32(&mut bytes)? != COLUMN_FORMAT {
+        return None;
+    }
+    let count = take_u32(&mut bytes)?;
+    let mut chunks = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let y = take_u32(&mut bytes)? as i32;
+        let length = take_u32(&mut bytes)? as usize;
+        if bytes.len() < length {
+            return None;
+        }
+        let (encoded, rest) = bytes.split_at(length);
+        bytes = rest;
+        chunks.push((y, encoded));
+    }
+    Some(chunks)
+}
+
+#[cfg(test)]
+#[test]
+fn test_region_round_trip() {
+    let directory = std::env::temp_dir().join(format!("region-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+
+    use crate::simulation::chunk::Block;
+
+    let blocks = |chunk: &Chunk| {
+        let mut all = vec![];
+        for x in 0..Chunk::SIZE {
+            for y in 0..Chunk::SIZE {
+                for z in 0..Chunk::SIZE {
+                    all.push(chunk.get(x, y, z));
+                }
+            }
+        }
+        all
+    };
+
+    let mut chunk = Chunk::default();
+    chunk.set(1, 2, 3, Block::named("stone"));
+    chunk.set(15, 15, 15, Block::named("sand"));
+
+    let mut noisy = Chunk::default();
+    for i in (0..Chunk::MAX_BLOCK_COUNT as usize).step_by(2) {
+        let block = Block::named(["stone", "dirt", "water"][i % 3]);
+        noisy.set(i / 256, i / 16 % 16, i % 16, block);
+    }
+
+    {
+        let mut storage = RegionStorage::open(&directory).unwrap();
+        storage.store_column(-1, 40, &[(3, &chunk)]).unwrap();
+        storage
+            .store_column(-1, 40, &[(-2, &Chunk::default())])
+            .unwrap();
+        storage.store_column(0, 0, &[(0, &chunk)]).unwrap();
+        storage.store_column(1, 0, &[(0, &chunk)]).unwrap();
+        // doesn't fit into the old sector anymore
+        let noisy_column = (0..8).map(|y| (y, &noisy)).collect::<Vec<_>>();
+        storage.store_column(0, 0, &noisy_column).unwrap();
+        storage.flush().unwrap();
+    }
+
+    let mut storage = RegionStorage::open(&directory).unwrap();
+    let column = storage.load_column(-1, 40).unwrap();
+    assert_eq!(column.iter().map(|it| it.0).collect::<Vec<_>>(), [-2, 3]);
+    assert_eq!(blocks(&column[1].1), blocks(&chunk));
+    assert_eq!(column[1].1.non_air_block_count, 2);
+    let noisy_column = storage.load_column(0, 0).unwrap();
+    assert_eq!(noisy_column.len(), 8);
+    assert_eq!(blocks(&noisy_column[7].1), blocks(&noisy));
+    assert_eq!(
+        blocks(&storage.load_column(1, 0).unwrap()[0].1),
+        blocks(&chunk)
+    );
+    assert!(storage.load_column(2, 0).unwrap().is_empty());
+    let single = storage.load_chunk(IVec3::new(-1, 3, 40)).unwrap().unwrap();
+    assert_eq!(blocks(&single), blocks(&chunk));
+    assert!(storage.load_chunk(IVec3::new(-1, 4, 40)).unwrap().is_none());
+    assert!(storage.load_chunk(IVec3::new(2, 0, 0)).unwrap().is_none());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
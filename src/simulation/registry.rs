@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use crate::simulation::chunk::Block;
+
+static REGISTRY: LazyLock<BlockRegistry> =
+    LazyLock::new(|| BlockRegistry::parse(include_str!("blocks.txt")).unwrap());
+
+pub struct BlockRegistry {
+    blocks: Vec<BlockProperties>,
+    by_name: HashMap<String, Block>,
+    /// Water blocks indexed by their fluid level
+    fluids: Vec<Block>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockProperties {
+    pub name: String,
+    /// Atlas tile of each face in the order +x, -x, +y, -y, +z, -z
+    pub tiles: [[u8; 2]; 6],
+    pub solid: bool,
+    pub transparent: bool,
+    pub collision: bool,
+    pub hardness: f32,
+    /// Falls down when the block below is removed.
+    pub falls: bool,
+    /// Emitted block light from 0 to 15
+    pub light: u8,
+    /// Is updated at random times, see [`World::tick`](crate::simulation::world::World::tick).
+    pub random_ticks: bool,
+    /// 0 for sources, larger values are further away from a source.
+    pub fluid_level: Option<u8>,
+    /// Average color of the tiles, used for voxel models
+    pub color: Option<[u8; 3]>,
+}
+
+impl BlockRegistry {
+    pub fn get() -> &'static BlockRegistry {
+        &REGISTRY
+    }
+
+    pub fn properties(&self, block: Block) -> &BlockProperties {
+        &self.blocks[block.id() as usize]
+    }
+
+    /// Returns `None` for ids that aren't registered, like ids that come from other workers.
+    pub fn by_id(&self, id: u32) -> Option<Block> {
+        (id < self.blocks.len() as u32).then(|| Block::from_id(id as u8))
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<Block> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn fluid(&self, level: u8) -> Option<Block> {
+        self.fluids.get(level as usize).copied()
+    }
+
+    pub fn max_fluid_level(&self) -> u8 {
+        self.fluids.len() as u8 - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// See `blocks.txt` for the format.
+    pub fn parse(definitions: &str) -> Result<Self, String> {
+        let mut blocks = Vec::<BlockProperties>::new();
+
+        for (number, line) in definitions.lines().enumerate() {
+            let error = |message: &str| Err(format!("line {}: {message}: {line}", number + 1));
+
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|it| it.strip_suffix(']')) {
+                if blocks.iter().any(|it| it.name == name) {
+                    return error("duplicate block");
+                }
+                blocks.push(BlockProperties {
+                    name: name.to_string(),
+                    tiles: [[0, 0]; 6],
+                    solid: true,
+                    transparent: false,
+                    collision: true,
+                    hardness: 1.0,
+                    falls: false,
+                    light: 0,
+                    random_ticks: false,
+                    fluid_level: None,
+                    color: None,
+                });
+                continue;
+            }
+
+            let Some(block) = blocks.last_mut() else {
+                return error("property outside of a block");
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                return error("expected key = value");
+            };
+            let value = value.trim();
+            let parse_bool = |value: &str| match value {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(()),
+            };
+
+            let result = match key.trim() {
+                "tiles" => parse_tiles(value).map(|it| block.tiles = it),
+                "solid" => parse_bool(value).map(|it| block.solid = it),
+                "transparent" => parse_bool(value).map(|it| block.transparent = it),
+                "collision" => parse_bool(value).map(|it| block.collision = it),
+                "hardness" => value.parse().map(|it| block.hardness = it).map_err(|_| ()),
+                "falls" => parse_bool(value).map(|it| block.falls = it),
+                "light" => value.parse().map(|it| block.light = it).map_err(|_| ()),
+                "random_ticks" => parse_bool(value).map(|it| block.random_ticks = it),
+                "fluid_level" => value
+                    .parse()
+                    .map(|it| block.fluid_level = Some(it))
+                    .map_err(|_| ()),
+                "color" => parse_color(value).map(|it| block.color = Some(it)),
+                _ => return error("unknown property"),
+            };
+            if result.is_err() {
+                return error("invalid value");
+            }
+        }
+
+        if blocks.first().map(|it| it.name.as_str()) != Some("air") {
+            return Err("the first block must be air".to_string());
+        }
+        if blocks.len() > u8::MAX as usize + 1 {
+            return Err(format!("too many blocks: {}", blocks.len()));
+        }
+
+        let mut fluids = vec![];
+        for (id, block) in blocks.iter().enumerate() {
+            if let Some(level) = block.fluid_level {
+                let level = level as usize;
+                if level >= fluids.len() {
+                    fluids.resize(level + 1, None);
+                }
+                if fluids[level].replace(Block::from_id(id as u8)).is_some() {
+                    return Err(format!("duplicate fluid level {level}"));
+                }
+            }
+        }
+        let Some(fluids) = fluids.into_iter().collect::<Option<Vec<_>>>() else {
+            return Err("fluid levels must start at 0 and not have gaps".to_string());
+        };
+        if fluids.is_empty() {
+            return Err("there must be a fluid".to_string());
+        }
+
+        let by_name = blocks
+            .iter()
+            .enumerate()
+            .map(|(id, it)| (it.name.clone(), Block::from_id(id as u8)))
+            .collect();
+        Ok(Self {
+            blocks,
+            by_name,
+            fluids,
+        })
+    }
+}
+
+fn parse_color(value: &str) -> Result<[u8; 3], ()> {
+    let rgb = value
+        .split_whitespace()
+        .map(|it| it.parse::<u8>().map_err(|_| ()))
+        .collect::<Result<Vec<_>, _>>()?;
+    rgb.try_into().map_err(|_| ())
+}
+
+fn parse_tiles(value: &str) -> Result<[[u8; 2]; 6], ()> {
+    let tiles = value
+        .split(',')
+        .map(|tile| {
+            let mut uv = tile.split_whitespace().map(|it| it.parse::<u8>());
+            match (uv.next(), uv.next(), uv.next()) {
+                (Some(Ok(u)), Some(Ok(v)), None) => Ok([u, v]),
+                _ => Err(()),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match tiles.len() {
+        1 => Ok([tiles[0]; 6]),
+        6 => Ok(tiles.try_into().unwrap()),
+        _ => Err(()),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_registry() {
+    let registry = BlockRegistry::get();
+    assert_eq!(registry.by_name("air"), Some(Block::AIR));
+    let grass = registry.properties(Block::named("grass"));
+    assert_eq!(grass.tiles[2], [0, 0]);
+    assert_eq!(grass.tiles[3], [0, 1]);
+    assert_eq!(grass.color, Some([62, 155, 35]));
+    assert!(!registry.properties(Block::AIR).solid);
+    assert_eq!(registry.by_id(0), Some(Block::AIR));
+    assert_eq!(registry.by_id(registry.len() as u32), None);
+    assert_eq!(registry.by_id(256), None);
+
+    assert_eq!(registry.fluid(0), Some(Block::named("water")));
+    assert_eq!(
+        registry
+            .fluid(registry.max_fluid_level())
+            .unwrap()
+            .properties()
+            .fluid_level,
+        Some(registry.max_fluid_level())
+    );
+
+    assert!(BlockRegistry::parse("[stone]").is_err());
+    assert!(BlockRegistry::parse("[air]\n[stone]\ntiles = 1 1, 1 1").is_err());
+    assert!(BlockRegistry::parse("[air]\n[stone]\nshiny = true").is_err());
+    assert!(BlockRegistry::parse("[air]\n[water]\nfluid_level = 1").is_err());
+    assert!(BlockRegistry::parse("[air]\n[stone]\ncolor = 1 2").is_err());
+}
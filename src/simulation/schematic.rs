@@ -0,0 +1,268 @@
+use std::path::Path;
+use std::{fs, io};
+
+use glam::IVec3;
+
+use crate::simulation::chunk::Block;
+use crate::simulation::position::BlockPosition;
+use crate::simulation::registry::BlockRegistry;
+use crate::simulation::selection::MAX_VOLUME;
+use crate::simulation::world::World;
+
+/// Layout of a schematic file:
+/// - `b"schm"`, `format: u16` and the size as `x: u16, y: u16, z: u16`
+/// - `palette_length: u16`, followed by the block names, each as `length: u8` and UTF-8 bytes.
+///   Names instead of ids keep the files valid when blocks are added.
+/// - runs of equal blocks until all `x * y * z` blocks are covered, each as `length: u16` and
+///   `palette_index: u8`. Blocks are ordered by x, then z, then y.
+///
+/// All integers are little endian.
+const MAGIC: &[u8; 4] = b"schm";
+const FORMAT: u16 = 1;
+
+/// A box of blocks that can be pasted somewhere else, e.g. the clipboard.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schematic {
+    size: IVec3,
+    blocks: Vec<Block>,
+}
+
+impl Schematic {
+    /// Copies the box between the corners, both are included. Blocks in chunks that aren't
+    /// loaded are copied as air.
+    pub fn copy(world: &World, a: BlockPosition, b: BlockPosition) -> Self {
+        let min = a.index().min(b.index());
+        let size = (a.index() - b.index()).abs() + 1;
+        let mut schematic = Self::filled(size, Block::AIR);
+        for p in schematic.positions() {
+            let block = world.get_block(BlockPosition::new(min + p));
+            schematic.blocks[index(size, p)] = block.unwrap_or(Block::AIR);
+        }
+        schematic
+    }
+
+    /// Only air
+    pub fn empty(size: IVec3) -> Self {
+        Self::filled(size, Block::AIR)
+    }
+
+    fn filled(size: IVec3, block: Block) -> Self {
+        Self {
+            size,
+            blocks: vec![block; size.element_product() as usize],
+        }
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    pub fn get(&self, position: IVec3) -> Block {
+        self.blocks[index(self.size, position)]
+    }
+
+    pub fn set(&mut self, position: IVec3, block: Block) {
+        self.blocks[index(self.size, position)] = block;
+    }
+
+    /// All positions in the order in which the blocks are stored
+    fn positions(&self) -> impl Iterator<Item = IVec3> + use<> {
+        let size = self.size;
+        (0..size.y).flat_map(move |y| {
+            (0..size.z).flat_map(move |z| (0..size.x).map(move |x| IVec3::new(x, y, z)))
+        })
+    }
+
+    /// Rotated by 90 degrees around the y axis, clockwise when seen from above.
+    pub fn rotated(&self) -> Self {
+        let size = IVec3::new(self.size.z, self.size.y, self.size.x);
+        let mut rotated = Self::filled(size, Block::AIR);
+        for p in self.positions() {
+            let new = IVec3::new(self.size.z - 1 - p.z, p.y, p.x);
+            rotated.blocks[index(size, new)] = self.get(p);
+        }
+        rotated
+    }
+
+    /// Mirrored along the x axis
+    pub fn mirrored(&self) -> Self {
+        let mut mirrored = self.clone();
+        for p in self.positions() {
+            let new = IVec3::new(self.size.x - 1 - p.x, p.y, p.z);
+            mirrored.blocks[index(self.size, new)] = self.get(p);
+        }
+        mirrored
+    }
+
+    /// Places the blocks with the smallest corner at `at`, air only if `with_air` is set. Blocks
+    /// that can't be broken and chunks that aren't loaded are skipped. Returns how many blocks
+    /// were changed.
+    pub fn paste(&self, world: &mut World, at: BlockPosition, with_air: bool) -> usize {
+        let mut changed = 0;
+        for p in self.positions() {
+            let position = at.plus(p);
+            let Some(previous) = world.get_block(position) else {
+                continue;
+            };
+            let block = self.get(p);
+            if previous != block
+                && previous.properties().hardness >= 0.0
+                && (with_air || block != Block::AIR)
+            {
+                world.set_block(position, block);
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        Self::decode(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn store(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let bytes = self
+            .encode()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        fs::write(path, bytes)
+    }
+
+    /// Fails for sizes and block names that don't fit into the format.
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        if self.size.cmpgt(IVec3::splat(u16::MAX as i32)).any() {
+            return Err(format!("size {} is larger than {}", self.size, u16::MAX));
+        }
+        let mut palette = Vec::<Block>::new();
+        for block in &self.blocks {
+            if !palette.contains(block) {
+                palette.push(*block);
+            }
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT.to_le_bytes());
+        for size in self.size.to_array() {
+            bytes.extend_from_slice(&(size as u16).to_le_bytes());
+        }
+        bytes.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+        for block in &palette {
+            let name = &block.properties().name;
+            let length = u8::try_from(name.len())
+                .map_err(|_| format!("block name {name} is longer than {} bytes", u8::MAX))?;
+            bytes.push(length);
+            bytes.extend_from_slice(name.as_bytes());
+        }
+
+        let mut blocks = self.blocks.iter().peekable();
+        while let Some(block) = blocks.next() {
+            let mut length = 1u16;
+            while length < u16::MAX && blocks.next_if_eq(&block).is_some() {
+                length += 1;
+            }
+            bytes.extend_from_slice(&length.to_le_bytes());
+            bytes.push(palette.iter().position(|it| it == block).unwrap() as u8);
+        }
+        Ok(bytes)
+    }
+
+    pub fn decode(mut bytes: &[u8]) -> Result<Self, String> {
+        let bytes = &mut bytes;
+        if take(bytes, 4)? != MAGIC {
+            return Err("not a schematic".to_string());
+        }
+        let format = take_u16(bytes)?;
+        if format != FORMAT {
+            return Err(format!("unknown format {format}"));
+        }
+        let mut size = IVec3::ZERO;
+        for axis in 0..3 {
+            size[axis] = take_u16(bytes)? as i32;
+        }
+        let palette_length = take_u16(bytes)?;
+
+        let mut palette = Vec::new();
+        for _ in 0..palette_length {
+            let length = take(bytes, 1)?[0] as usize;
+            let name = std::str::from_utf8(take(bytes, length)?).map_err(|e| e.to_string())?;
+            let block = BlockRegistry::get()
+                .by_name(name)
+                .ok_or_else(|| format!("unknown block {name}"))?;
+            palette.push(block);
+        }
+
+        if size.as_u64vec3().element_product() > MAX_VOLUME {
+            return Err(format!("size {size} is too large"));
+        }
+        let len = size.element_product() as usize;
+        let mut blocks = Vec::with_capacity(len);
+        while blocks.len() < len {
+            let length = take_u16(bytes)? as usize;
+            let index = take(bytes, 1)?[0] as usize;
+            let block = *palette.get(index).ok_or("palette index out of range")?;
+            blocks.extend(std::iter::repeat_n(block, length));
+        }
+        if blocks.len() != len || !bytes.is_empty() {
+            return Err("block data doesn't match the size".to_string());
+        }
+        Ok(Self { size, blocks })
+    }
+}
+
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], String> {
+    let (taken, rest) = bytes.split_at_checked(n).ok_or("unexpected end of file")?;
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn take_u16(bytes: &mut &[u8]) -> Result<u16, String> {
+    Ok(u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()))
+}
+
+fn index(size: IVec3, p: IVec3) -> usize {
+    (p.x + size.x * (p.z + size.z * p.y)) as usize
+}
+
+#[cfg(test)]
+#[test]
+fn test_schematic() {
+    use crate::simulation::world::at;
+
+    let mut world = World::test_world(None);
+    let stone = Block::named("stone");
+    let dirt = Block::named("dirt");
+    for x in 1..=3 {
+        world.set_block(at(x, 1, 1), stone);
+    }
+    world.set_block(at(3, 1, 2), dirt);
+
+    let schematic = Schematic::copy(&world, at(3, 1, 2), at(1, 1, 1));
+    assert_eq!(schematic.size(), IVec3::new(3, 1, 2));
+    assert_eq!(schematic.get(IVec3::new(2, 0, 1)), dirt);
+    assert_eq!(schematic.get(IVec3::new(0, 0, 1)), Block::AIR);
+
+    let rotated = schematic.rotated();
+    assert_eq!(rotated.size(), IVec3::new(2, 1, 3));
+    assert_eq!(rotated.get(IVec3::new(0, 0, 2)), dirt);
+    assert_eq!(rotated.rotated().rotated().rotated(), schematic);
+    let mirrored = schematic.mirrored();
+    assert_eq!(mirrored.get(IVec3::new(0, 0, 1)), dirt);
+    assert_eq!(mirrored.mirrored(), schematic);
+
+    let bytes = schematic.encode().unwrap();
+    // header, palette of stone, air and dirt, and 3 runs
+    assert_eq!(bytes.len(), 12 + 2 + 6 + 4 + 5 + 3 * 3);
+    assert_eq!(Schematic::decode(&bytes), Ok(schematic.clone()));
+    assert!(Schematic::decode(&bytes[..bytes.len() - 1]).is_err());
+    // copies are only limited by their volume
+    assert!(Schematic::empty(IVec3::new(70000, 1, 1)).encode().is_err());
+
+    assert_eq!(rotated.paste(&mut world, at(8, 4, 8), true), 4);
+    assert_eq!(world.get_block(at(8, 4, 10)), Some(dirt));
+    assert_eq!(world.get_block(at(9, 4, 8)), Some(stone));
+    assert_eq!(world.get_block(at(8, 4, 8)), Some(Block::AIR));
+}
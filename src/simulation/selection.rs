@@ -0,0 +1,167 @@
+use glam::IVec3;
+
+use crate::simulation::chunk::Block;
+use crate::simulation::position::BlockPosition;
+use crate::simulation::world::World;
+
+/// Larger operations are refused, so that a misplaced corner can't freeze the game.
+pub const MAX_VOLUME: u64 = 1 << 21;
+
+/// Bulk edits on the box between the two corners of a selection, both corners are included.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Operation {
+    Fill,
+    /// Replaces only the blocks that are equal to the block at the first corner
+    Replace,
+    /// The surface of the box
+    HollowBox,
+    /// The surface of the box without floor and ceiling
+    Walls,
+    /// From the first to the second corner
+    Line,
+    /// Vertical with an elliptic base that touches the sides of the box
+    Cylinder,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 6] = [
+        Operation::Fill,
+        Operation::Replace,
+        Operation::HollowBox,
+        Operation::Walls,
+        Operation::Line,
+        Operation::Cylinder,
+    ];
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    pub fn id(self) -> u32 {
+        Self::ALL.iter().position(|it| *it == self).unwrap() as u32
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Selection {
+    pub corners: [Option<BlockPosition>; 2],
+}
+
+impl Selection {
+    /// Sets the blocks of the operation to `block` and returns how many were changed. Blocks
+    /// that can't be broken and blocks in chunks that aren't loaded are skipped. The caller is
+    /// responsible for grouping the changes into an edit batch.
+    pub fn apply(&self, world: &mut World, operation: Operation, block: Block) -> Option<usize> {
+        let [Some(a), Some(b)] = self.corners else {
+            log::info!("Select two corners first");
+            return None;
+        };
+        let (a, b) = (a.index(), b.index());
+        let size = ((a - b).abs() + 1).as_u64vec3();
+        if operation != Operation::Line && size.element_product() > MAX_VOLUME {
+            log::warn!("Selection with a size of {size} is too large");
+            return None;
+        }
+
+        let replaced = world.get_block(BlockPosition::new(a));
+        let mut changed = 0;
+        for position in positions(operation, a, b) {
+            let position = BlockPosition::new(position);
+            let Some(previous) = world.get_block(position) else {
+                continue;
+            };
+            if previous.properties().hardness < 0.0
+                || (operation == Operation::Replace && Some(previous) != replaced)
+            {
+                continue;
+            }
+            changed += (world.set_block(position, block) != Some(block)) as usize;
+        }
+        Some(changed)
+    }
+}
+
+/// Positions that are affected by the operation, each at most once
+fn positions(operation: Operation, a: IVec3, b: IVec3) -> Vec<IVec3> {
+    let min = a.min(b);
+    let max = a.max(b);
+    let in_box = || {
+        (min.x..=max.x).flat_map(move |x| {
+            (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+        })
+    };
+    match operation {
+        Operation::Fill | Operation::Replace => in_box().collect(),
+        Operation::HollowBox => in_box()
+            .filter(|p| p.cmpeq(min).any() || p.cmpeq(max).any())
+            .collect(),
+        Operation::Walls => in_box()
+            .filter(|p| p.x == min.x || p.x == max.x || p.z == min.z || p.z == max.z)
+            .collect(),
+        Operation::Line => {
+            let steps = (b - a).abs().max_element();
+            let delta = (b - a).as_vec3();
+            (0..=steps)
+                .map(|i| {
+                    let t = if steps == 0 {
+                        0.0
+                    } else {
+                        i as f32 / steps as f32
+                    };
+                    a + (delta * t).round().as_ivec3()
+                })
+                .collect()
+        }
+        Operation::Cylinder => {
+            let center = (min + max).as_vec3() / 2.0;
+            let radius = (max - min).as_vec3() / 2.0 + 0.5;
+            in_box()
+                .filter(|p| {
+                    let d = (p.as_vec3() - center) / radius;
+                    d.x * d.x + d.z * d.z <= 1.0
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_operations() {
+    use crate::simulation::world::at;
+
+    let count = |operation, b| positions(operation, IVec3::ZERO, b).len();
+    let b = IVec3::new(4, 3, 5);
+    assert_eq!(count(Operation::Fill, b), 5 * 4 * 6);
+    assert_eq!(count(Operation::HollowBox, b), 5 * 4 * 6 - 3 * 2 * 4);
+    assert_eq!(count(Operation::Walls, b), (5 * 6 - 3 * 4) * 4);
+    assert_eq!(count(Operation::Line, b), 6);
+    assert_eq!(count(Operation::Line, IVec3::ZERO), 1);
+    // a circle with a diameter of 5 blocks
+    assert_eq!(count(Operation::Cylinder, IVec3::new(4, 0, 4)), 21);
+    let line = positions(Operation::Line, b, IVec3::ZERO);
+    assert_eq!((line[0], line[5]), (b, IVec3::ZERO));
+
+    let mut world = World::test_world(None);
+    let stone = Block::named("stone");
+    let dirt = Block::named("dirt");
+    let mut selection = Selection::default();
+    assert_eq!(selection.apply(&mut world, Operation::Fill, stone), None);
+
+    selection.corners = [Some(at(1, 1, 1)), Some(at(3, 3, 3))];
+    assert_eq!(
+        selection.apply(&mut world, Operation::Fill, stone),
+        Some(27)
+    );
+    world.set_block(at(2, 2, 2), Block::AIR);
+    // the first corner is stone
+    assert_eq!(
+        selection.apply(&mut world, Operation::Replace, dirt),
+        Some(26)
+    );
+    assert_eq!(world.get_block(at(2, 2, 2)), Some(Block::AIR));
+    assert_eq!(world.get_block(at(3, 1, 2)), Some(dirt));
+
+    selection.corners[1] = Some(at(3000, 3000, 3));
+    assert_eq!(selection.apply(&mut world, Operation::Fill, stone), None);
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::simulation::position::BlockPosition;
+use crate::timer::Timer;
+
+/// Length of a game tick
+pub const TICK: Duration = Duration::from_millis(50);
+
+/// If the worker was blocked for longer, the missed ticks are dropped instead of running all of
+/// them at once.
+const MAX_CATCH_UP: u64 = 10;
+
+/// Keeps the game ticks at a fixed rate in real time.
+pub struct TickClock {
+    start: Timer,
+    ticks: u64,
+}
+
+impl TickClock {
+    pub fn new() -> Self {
+        Self {
+            start: Timer::now(),
+            ticks: 0,
+        }
+    }
+
+    /// Returns how many ticks should run now and counts them as done.
+    pub fn due(&mut self) -> u64 {
+        let total = (self.start.elapsed().as_nanos() / TICK.as_nanos()) as u64;
+        let due = total - self.ticks;
+        self.ticks = total;
+        due.min(MAX_CATCH_UP)
+    }
+
+    pub fn until_next(&self) -> Duration {
+        let next = Duration::from_nanos((TICK.as_nanos() * (self.ticks as u128 + 1)) as u64);
+        next.saturating_sub(self.start.elapsed())
+    }
+}
+
+/// Block updates that run at a later game tick.
+#[derive(Default)]
+pub struct ScheduledTicks {
+    current: u64,
+    queue: BTreeMap<u64, Vec<BlockPosition>>,
+}
+
+impl ScheduledTicks {
+    /// `delay` is in ticks. Updates are never run in the tick in which they are scheduled.
+    pub fn schedule(&mut self, position: BlockPosition, delay: u64) {
+        let tick = self.current + delay.max(1);
+        self.queue.entry(tick).or_default().push(position);
+    }
+
+    /// Advances to the next tick and returns the updates that are due, possibly with duplicates.
+    pub fn advance(&mut self) -> Vec<BlockPosition> {
+        self.current += 1;
+        self.queue.remove(&self.current).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_scheduled_ticks() {
+    use glam::IVec3;
+
+    let mut ticks = ScheduledTicks::default();
+    let a = BlockPosition::new(IVec3::X);
+    let b = BlockPosition::new(IVec3::Y);
+    ticks.schedule(a, 2);
+    ticks.schedule(b, 0);
+    ticks.schedule(a, 1);
+
+    assert_eq!(ticks.advance(), vec![b, a]);
+    assert_eq!(ticks.advance(), vec![a]);
+    assert_eq!(ticks.current, 2);
+    assert!(ticks.advance().is_empty());
+}
@@ -0,0 +1,280 @@
+use std::path::Path;
+use std::{fs, io};
+
+use glam::IVec3;
+
+use crate::simulation::chunk::Block;
+use crate::simulation::registry::BlockRegistry;
+use crate::simulation::schematic::Schematic;
+
+/// MagicaVoxel files consist of `b"VOX "`, `version: i32` and a `MAIN` chunk. Every chunk is
+/// `id: [u8; 4], content_length: i32, children_length: i32`, followed by the content and the
+/// children. The children of `MAIN` are read:
+/// - `SIZE`: `x: i32, y: i32, z: i32` of a model, the z axis points up
+/// - `XYZI`: `count: i32` voxels of the model, each as `x: u8, y: u8, z: u8, color_index: u8`
+/// - `RGBA`: 256 colors as `r, g, b, a`, color index `i` uses entry `i - 1`
+///
+/// Only the first model is imported, other chunks like the scene graph are skipped. All integers
+/// are little endian.
+const MAGIC: &[u8; 4] = b"VOX ";
+const VERSION: i32 = 150;
+/// Models can't be larger in MagicaVoxel
+const MAX_SIZE: i32 = 256;
+
+/// Reads the first model of a `.vox` file. Every voxel becomes the block with the nearest
+/// [`color`](crate::simulation::registry::BlockProperties::color), empty space is air.
+pub fn decode(mut bytes: &[u8]) -> Result<Schematic, String> {
+    let bytes = &mut bytes;
+    if take(bytes, 4)? != MAGIC {
+        return Err("not a vox file".to_string());
+    }
+    take_i32(bytes)?;
+    let main = take_chunk(bytes)?;
+    if main.id != b"MAIN" {
+        return Err("missing MAIN chunk".to_string());
+    }
+
+    let mut size = None;
+    let mut voxels = None;
+    let mut palette = default_palette();
+    let mut children = main.children;
+    while !children.is_empty() {
+        let chunk = take_chunk(&mut children)?;
+        let mut content = chunk.content;
+        match chunk.id {
+            b"SIZE" if size.is_none() => {
+                let mut s = IVec3::ZERO;
+                for axis in 0..3 {
+                    s[axis] = take_i32(&mut content)?;
+                }
+                if s.cmplt(IVec3::ONE).any() || s.cmpgt(IVec3::splat(MAX_SIZE)).any() {
+                    return Err(format!("invalid size {s}"));
+                }
+                size = Some(s);
+            }
+            b"XYZI" if voxels.is_none() => {
+                let count = take_i32(&mut content)?;
+                let data = take(&mut content, count.max(0) as usize * 4)?;
+                voxels = Some(data.chunks_exact(4).map(|it| [it[0], it[1], it[2], it[3]]));
+            }
+            b"RGBA" => {
+                let data = take(&mut content, 256 * 4)?;
+                for (i, color) in data.chunks_exact(4).enumerate().take(255) {
+                    palette[i + 1] = [color[0], color[1], color[2]];
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (Some(size), Some(voxels)) = (size, voxels) else {
+        return Err("missing SIZE or XYZI chunk".to_string());
+    };
+    let blocks = block_palette(&palette);
+    let mut schematic = Schematic::empty(IVec3::new(size.x, size.z, size.y));
+    for [x, y, z, color] in voxels {
+        let p = IVec3::new(x as i32, y as i32, z as i32);
+        if p.cmpge(size).any() {
+            return Err(format!("voxel {p} outside of the model"));
+        }
+        schematic.set(to_block_space(p, size), blocks[color as usize]);
+    }
+    Ok(schematic)
+}
+
+/// Writes all blocks apart from air as voxels with the color of the block, or grey if it
+/// doesn't have one.
+pub fn encode(schematic: &Schematic) -> Result<Vec<u8>, String> {
+    let size = schematic.size();
+    if size.cmpgt(IVec3::splat(MAX_SIZE)).any() {
+        return Err(format!("size {size} is larger than {MAX_SIZE}"));
+    }
+    let vox_size = IVec3::new(size.x, size.z, size.y);
+
+    // the color index of a block is its id, index 0 is unused
+    let registry = BlockRegistry::get();
+    let mut rgba = Vec::with_capacity(256 * 4);
+    for index in 1..=256 {
+        let color = (index < registry.len())
+            .then(|| Block::from_id(index as u8).properties().color)
+            .flatten()
+            .unwrap_or([128; 3]);
+        rgba.extend_from_slice(&color);
+        rgba.push(255);
+    }
+
+    let mut xyzi = vec![0; 4];
+    let mut count = 0i32;
+    for x in 0..size.x {
+        for y in 0..size.y {
+            for z in 0..size.z {
+                let block = schematic.get(IVec3::new(x, y, z));
+                if block != Block::AIR {
+                    let p = IVec3::new(x, size.z - 1 - z, y);
+                    xyzi.extend_from_slice(&[p.x as u8, p.y as u8, p.z as u8, block.id()]);
+                    count += 1;
+                }
+            }
+        }
+    }
+    xyzi[0..4].copy_from_slice(&count.to_le_bytes());
+
+    let mut children = Vec::new();
+    let size_content = vox_size.to_array().map(i32::to_le_bytes).concat();
+    write_chunk(&mut children, b"SIZE", &size_content, &[]);
+    write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+    write_chunk(&mut children, b"RGBA", &rgba, &[]);
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    write_chunk(&mut bytes, b"MAIN", &[], &children);
+    Ok(bytes)
+}
+
+pub fn load(path: impl AsRef<Path>) -> io::Result<Schematic> {
+    let bytes = fs::read(path)?;
+    decode(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn store(schematic: &Schematic, path: impl AsRef<Path>) -> io::Result<()> {
+    let bytes = encode(schematic).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    if let Some(parent) = path.as_ref().parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, bytes)
+}
+
+/// MagicaVoxel has the z axis up, blocks the y axis.
+fn to_block_space(p: IVec3, size: IVec3) -> IVec3 {
+    IVec3::new(p.x, p.z, size.y - 1 - p.y)
+}
+
+/// The block for every color index. Only blocks that can be broken and don't flow are used, so
+/// that pasting an import can be undone by hand.
+fn block_palette(palette: &[[u8; 3]; 256]) -> [Block; 256] {
+    let registry = BlockRegistry::get();
+    let colored = (0..registry.len())
+        .map(|id| Block::from_id(id as u8))
+        .filter(|block| {
+            let properties = block.properties();
+            properties.hardness >= 0.0 && properties.fluid_level.is_none()
+        })
+        .filter_map(|block| Some((block, block.properties().color?)))
+        .collect::<Vec<_>>();
+    palette.map(|color| {
+        colored
+            .iter()
+            .min_by_key(|(_, c)| {
+                (0..3)
+                    .map(|i| (c[i] as i32 - color[i] as i32).pow(2))
+                    .sum::<i32>()
+            })
+            .map_or(Block::AIR, |(block, _)| *block)
+    })
+}
+
+/// Used when a file doesn't contain an `RGBA` chunk. Index 0 is unused, then follow all
+/// combinations of the channel values `ff, cc, 99, 66, 33, 00` apart from black, and ramps of
+/// red, green, blue and grey.
+fn default_palette() -> [[u8; 3]; 256] {
+    let mut palette = [[0; 3]; 256];
+    let mut i = 1;
+    for r in (0..6).rev() {
+        for g in (0..6).rev() {
+            for b in (0..6).rev() {
+                if (r, g, b) != (0, 0, 0) {
+                    palette[i] = [r * 0x33, g * 0x33, b * 0x33];
+                    i += 1;
+                }
+            }
+        }
+    }
+    let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    for mask in [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 1]] {
+        for value in ramp {
+            palette[i] = mask.map(|it| it * value);
+            i += 1;
+        }
+    }
+    debug_assert_eq!(i, 256);
+    palette
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    bytes.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    bytes.extend_from_slice(content);
+    bytes.extend_from_slice(children);
+}
+
+struct VoxChunk<'a> {
+    id: &'a [u8; 4],
+    content: &'a [u8],
+    children: &'a [u8],
+}
+
+fn take_chunk<'a>(bytes: &mut &'a [u8]) -> Result<VoxChunk<'a>, String> {
+    let id = take(bytes, 4)?.try_into().unwrap();
+    let content_length = take_i32(bytes)?;
+    let children_length = take_i32(bytes)?;
+    if content_length < 0 || children_length < 0 {
+        return Err("invalid chunk length".to_string());
+    }
+    let content = take(bytes, content_length as usize)?;
+    let children = take(bytes, children_length as usize)?;
+    Ok(VoxChunk {
+        id,
+        content,
+        children,
+    })
+}
+
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], String> {
+    let (taken, rest) = bytes.split_at_checked(n).ok_or("unexpected end of file")?;
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn take_i32(bytes: &mut &[u8]) -> Result<i32, String> {
+    Ok(i32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))
+}
+
+#[cfg(test)]
+#[test]
+fn test_vox() {
+    let stone = Block::named("stone");
+    let grass = Block::named("grass");
+    let mut schematic = Schematic::empty(IVec3::new(3, 4, 2));
+    schematic.set(IVec3::new(0, 0, 0), stone);
+    schematic.set(IVec3::new(2, 3, 1), grass);
+    schematic.set(IVec3::new(1, 0, 1), Block::named("lamp"));
+
+    let bytes = encode(&schematic).unwrap();
+    assert_eq!(decode(&bytes), Ok(schematic));
+    assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+    assert!(encode(&Schematic::empty(IVec3::new(1, 300, 1))).is_err());
+
+    // without RGBA chunk and with a chunk that is skipped
+    let mut children = Vec::new();
+    write_chunk(
+        &mut children,
+        b"SIZE",
+        &[[1, 0, 0, 0], [2, 0, 0, 0], [3, 0, 0, 0]].concat(),
+        &[],
+    );
+    write_chunk(&mut children, b"nTRN", &[0; 8], &[]);
+    // white at the bottom and green at the top
+    let xyzi = [[2, 0, 0, 0], [0, 0, 0, 1], [0, 1, 2, 186]].concat();
+    write_chunk(&mut children, b"XYZI", &xyzi, &[]);
+    let mut bytes = [MAGIC.as_slice(), &VERSION.to_le_bytes()].concat();
+    write_chunk(&mut bytes, b"MAIN", &[], &children);
+
+    let model = decode(&bytes).unwrap();
+    assert_eq!(model.size(), IVec3::new(1, 3, 2));
+    // the unbreakable button is white as well
+    assert_eq!(model.get(IVec3::new(0, 0, 1)), Block::named("lamp"));
+    assert_eq!(model.get(IVec3::new(0, 2, 0)), grass);
+    assert_eq!(model.get(IVec3::new(0, 0, 0)), Block::AIR);
+}
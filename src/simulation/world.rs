@@ -2,70 +2,151 @@ use std::collections::{HashMap, HashSet, VecDeque};
 use std::mem;
 
 use glam::{IVec3, Vec3};
+use rand::RngExt;
+use rand::rngs::StdRng;
 
+use crate::generator::meshing;
 use crate::renderer::MeshData;
-use crate::renderer::mesh::{ChunkMesh, Vertex};
+use crate::renderer::mesh::Meshing;
 use crate::simulation::chunk::{Block, Chunk, Transparency};
+use crate::simulation::history::{Edit, EditHistory};
 use crate::simulation::position::{BlockPosition, ChunkPosition};
+use crate::simulation::region::RegionStorage;
+use crate::simulation::tick::ScheduledTicks;
+use crate::simulation::{fluid, grass, gravity, light};
 
 #[allow(unused)]
 pub struct World {
-    view_distance: u16,
-    pub highest_generated_chunk: i32,
-    pub lowest_generated_chunk: i32,
+    /// Horizontal radius and vertical view distance in chunks
+    view_distance: IVec3,
     chunks: Vec<Chunk>,
     position_to_index: HashMap<ChunkPosition, ChunkIndex>,
     position_has_mesh: HashSet<ChunkPosition>,
-    generation_queue: VecDeque<(i32, i32, i32)>,
+    /// Chunks that still have to be generated, sorted so that the most important one is last
+    generation_queue: Vec<ChunkPosition>,
+    generation_queue_sorted: bool,
+    generation_focus: GenerationFocus,
+    /// Chunks that were handed out for generation and haven't come back yet
+    generating: HashSet<ChunkPosition>,
     mesh_queue: VecDeque<ChunkPosition>,
+    light_queue: VecDeque<ChunkPosition>,
     free_chunk_indices: Vec<ChunkIndex>,
+    /// Positions of changed blocks and their neighbours that haven't reacted yet
+    neighbour_updates: Vec<BlockPosition>,
+    scheduled_ticks: ScheduledTicks,
+    history: EditHistory,
+    meshing: Meshing,
+    /// Given to chunks when their mesh has to be updated
+    next_mesh_version: u32,
     //simulation_regions: Vec<SimulationRegion>,
-    min: (i32, i32),
-    max: (i32, i32),
+    /// Center of the chunks that were requested for generation
+    center: ChunkPosition,
+    storage: Option<RegionStorage>,
 }
 
 impl World {
-    pub fn new(view_distance: u16, height: u16) -> Self {
+    pub fn new(
+        view_distance: u16,
+        vertical_view_distance: u16,
+        around: ChunkPosition,
+        storage: Option<RegionStorage>,
+    ) -> Self {
         let mut chunk = Chunk::default();
         chunk.transparency = !0u8;
 
-        // 2 -> -1..=0
-        // 3 -> -1..=1
-        // 4 -> -2..=1
-        // 5 -> -2..=2
-        let lowest_generated_chunk = -(height as i32) / 2;
-        let highest_generated_chunk = lowest_generated_chunk + height as i32 - 1;
-
-        let mut generation_queue = Vec::<(i32, i32, i32)>::new();
-        let v = view_distance as i32;
-        for x in -v..=v {
-            for z in -v..=v {
-                generation_queue.push((x, highest_generated_chunk, z));
-            }
-        }
-        generation_queue.sort_by_key(|(x, _, z)| x * x + z * z);
-
-        Self {
-            view_distance,
-            highest_generated_chunk,
-            lowest_generated_chunk,
+        let mut world = Self {
+            view_distance: view_distance_vector(view_distance, vertical_view_distance),
             chunks: vec![chunk],
             position_to_index: Default::default(),
             position_has_mesh: HashSet::default(),
-            generation_queue: VecDeque::from(generation_queue),
+            generation_queue: Vec::new(),
+            generation_queue_sorted: false,
+            generation_focus: GenerationFocus {
+                chunk: around,
+                view: Vec3::ZERO,
+                travel: Vec3::ZERO,
+            },
+            generating: HashSet::new(),
             mesh_queue: VecDeque::new(),
+            light_queue: VecDeque::new(),
             free_chunk_indices: Vec::new(),
-            min: (-v, -v),
-            max: (v, v),
+            neighbour_updates: Vec::new(),
+            scheduled_ticks: ScheduledTicks::default(),
+            history: EditHistory::default(),
+            meshing: Meshing::default(),
+            next_mesh_version: 0,
+            center: around,
+            storage,
+        };
+        world.queue_missing_chunks();
+        world
+    }
+
+    pub fn storage(&self) -> Option<&RegionStorage> {
+        self.storage.as_ref()
+    }
+
+    /// Generation prefers chunks in the direction of `view` and `travel`, which don't have to be
+    /// normalized.
+    pub fn set_generation_focus(&mut self, chunk: ChunkPosition, view: Vec3, travel: Vec3) {
+        let focus = GenerationFocus {
+            chunk,
+            view: view.normalize_or_zero(),
+            travel: travel.normalize_or_zero(),
+        };
+        // sorting the whole queue again is only worth it for noticeable changes
+        let similar = |a: Vec3, b: Vec3| a.dot(b) > 0.95 || a == b;
+        if focus.chunk != self.generation_focus.chunk
+            || !similar(focus.view, self.generation_focus.view)
+            || !similar(focus.travel, self.generation_focus.travel)
+        {
+            self.generation_focus = focus;
+            self.generation_queue_sorted = false;
         }
     }
 
-    pub fn next_column_to_generate(&mut self) -> Option<(i32, i32)> {
-        self.generation_queue.pop_front().map(|(x, _, z)| (x, z))
+    /// Returns the most important chunk that isn't generated yet and counts it as generating.
+    pub fn next_chunk_to_generate(&mut self) -> Option<ChunkPosition> {
+        if !self.generation_queue_sorted {
+            let focus = self.generation_focus;
+            self.generation_queue
+                .sort_by(|a, b| focus.priority(*b).total_cmp(&focus.priority(*a)));
+            self.generation_queue_sorted = true;
+        }
+        let position = self.generation_queue.pop()?;
+        self.generating.insert(position);
+        Some(position)
+    }
+
+    /// Number of chunks that wait in the generation queue
+    pub fn queued_count(&self) -> usize {
+        self.generation_queue.len()
+    }
+
+    /// Number of chunks that were handed out by [`World::next_chunk_to_generate`] and haven't
+    /// finished yet
+    pub fn generating_count(&self) -> usize {
+        self.generating.len()
+    }
+
+    /// Returns false if the chunk isn't needed anymore because it left the view distance while
+    /// it was generated, then it should be dropped.
+    pub fn finish_generation(&mut self, position: ChunkPosition) -> bool {
+        self.generating.remove(&position);
+        self.in_range(position)
+    }
+
+    /// Whether the chunk is within the view distance of the current center. The loaded region is
+    /// round horizontally and has a fixed height.
+    fn in_range(&self, position: ChunkPosition) -> bool {
+        in_view(self.center, self.view_distance, position)
     }
 
-    pub fn get_updated_meshes(&mut self) -> Vec<(MeshData, Vec<Vertex>, Vec<u16>)> {
-        let mut result = Vec::with_capacity(self.mesh_queue.len());
+    /// Returns the meshes of chunks that don't have any visible faces, and jobs for the
+    /// generator workers to mesh the others.
+    pub fn get_updated_meshes(&mut self) -> (Vec<MeshData>, Vec<Box<[u8]>>) {
+        let mut empty = Vec::new();
+        let mut jobs = Vec::new();
         while let Some(position) = self.mesh_queue.pop_front() {
             if self.get_chunk(position).is_none() {
                 continue; // not loaded anymore. Should be safe to ignore
@@ -74,18 +155,17 @@ impl World {
             self.get_chunk_mut(position, false).unwrap().in_mesh_queue = false;
 
             let chunk = self.get_chunk(position).unwrap();
+            let mut mesh_data = MeshData {
+                chunk: position.index().to_array(),
+                vertex_count: 0,
+                index_count: 0,
+                is_full_and_invisible: 0,
+                large_indices: 0,
+                version: chunk.mesh_version,
+            };
 
             if chunk.non_air_block_count == 0 {
-                result.push((
-                    MeshData {
-                        chunk: position.index().to_array(),
-                        vertex_count: 0,
-                        index_count: 0,
-                        is_full_and_invisible: 0,
-                    },
-                    vec![],
-                    vec![],
-                ));
+                empty.push(mesh_data);
                 continue; // invisible
             }
 
@@ -103,60 +183,93 @@ impl World {
                 && !neighbours.pos_z.get_transparency(Transparency::NegZ)
                 && !neighbours.neg_z.get_transparency(Transparency::PosZ)
             {
-                result.push((
-                    MeshData {
-                        chunk: position.index().to_array(),
-                        vertex_count: 0,
-                        index_count: 0,
-                        is_full_and_invisible: 1,
-                    },
-                    vec![],
-                    vec![],
-                ));
+                mesh_data.is_full_and_invisible = 1;
+                empty.push(mesh_data);
                 continue;
             }
 
-            let (vertices, indices) = ChunkMesh::generate(&chunk, neighbours);
-            result.push((
-                MeshData {
-                    chunk: position.index().to_array(),
-                    vertex_count: vertices.len() as u32,
-                    index_count: indices.len() as u32,
-                    is_full_and_invisible: 0,
-                },
-                vertices,
-                indices,
+            jobs.push(meshing::encode_job(
+                position,
+                chunk.mesh_version,
+                self.meshing,
+                chunk,
+                neighbours,
             ));
         }
 
-        result
+        (empty, jobs)
     }
 
+    /// Unloads the chunks that are out of view from `around` and returns their positions.
     pub fn crop(&mut self, around: ChunkPosition) -> Vec<ChunkPosition> {
-        let v = self.view_distance as i32;
-
-        self.min.0 = self.min.0.max(around.index().x - v);
-        self.min.1 = self.min.1.max(around.index().z - v);
+        let view_distance = self.view_distance;
 
-        self.max.0 = self.max.0.min(around.index().x + v);
-        self.max.1 = self.max.1.min(around.index().z + v);
+        // cancel the requests that haven't been sent to a worker yet
+        self.generation_queue
+            .retain(|p| in_view(around, view_distance, *p));
 
         let mut removed = Vec::new();
+        let mut evicted = Vec::new();
         self.position_to_index.retain(|p, index| {
-            let x = p.index().x >= self.min.0 && p.index().x <= self.max.0;
-            let z = p.index().z >= self.min.1 && p.index().z <= self.max.1;
-            let retain = x && z;
-            if !retain {
-                if index.0 != 0 {
-                    removed.push(*p);
-                    self.free_chunk_indices.push(*index);
-                }
+            let retain = in_view(around, view_distance, *p);
+            if !retain && index.0 != 0 {
+                removed.push(*p);
+                evicted.push((*p, *index));
+                self.free_chunk_indices.push(*index);
             }
             retain
         });
+        self.store_modified_chunks(evicted);
         removed
     }
 
+    /// Stores all modified chunks that are currently loaded, e.g. before shutting down.
+    pub fn save(&mut self) {
+        let loaded = self
+            .position_to_index
+            .iter()
+            .map(|(p, i)| (*p, *i))
+            .collect::<Vec<_>>();
+        self.store_modified_chunks(loaded.iter().copied());
+        for (_, index) in loaded {
+            self.chunks[index.0 as usize].modified = false;
+        }
+        if let Some(Err(e)) = self.storage.as_mut().map(RegionStorage::flush) {
+            log::error!("Could not flush region files: {e}");
+        }
+    }
+
+    fn store_modified_chunks(
+        &mut self,
+        chunks: impl IntoIterator<Item = (ChunkPosition, ChunkIndex)>,
+    ) {
+        let Some(storage) = &mut self.storage else {
+            return;
+        };
+        let mut columns = HashMap::<(i32, i32), Vec<(i32, &Chunk)>>::new();
+        for (position, index) in chunks {
+            let chunk = &self.chunks[index.0 as usize];
+            if chunk.modified {
+                let p = position.index();
+                columns.entry((p.x, p.z)).or_default().push((p.y, chunk));
+            }
+        }
+        for ((x, z), chunks) in columns {
+            if let Err(e) = storage.store_column(x, z, &chunks) {
+                log::error!("Could not store column ({x}, {z}): {e}");
+            }
+        }
+    }
+
+    /// The chunk if it was modified in an earlier session or before it was cropped.
+    pub fn load_stored_chunk(&mut self, position: ChunkPosition) -> Option<Chunk> {
+        let storage = self.storage.as_mut()?;
+        storage.load_chunk(position.index()).unwrap_or_else(|e| {
+            log::error!("Could not load chunk {position:?}: {e}");
+            None
+        })
+    }
+
     pub fn add_chunk(&mut self, position: ChunkPosition, chunk: Chunk) {
         let index = if let Some(index) = self.free_chunk_indices.pop() {
             self.chunks[index.0 as usize] = chunk;
@@ -169,12 +282,16 @@ impl World {
         self.position_to_index.insert(position, index);
         self.request_mesh_update(position);
         self.request_neighbour_mesh_updates(position);
+        self.request_light_update(position);
+        self.request_neighbour_light_updates(position);
     }
 
     pub fn add_air_chunk(&mut self, position: ChunkPosition) {
         let index = ChunkIndex(0);
         self.position_to_index.insert(position, index);
         self.request_neighbour_mesh_updates(position);
+        self.request_light_update(position);
+        self.request_neighbour_light_updates(position);
     }
 
     pub fn get_chunk(&self, position: ChunkPosition) -> Option<&Chunk> {
@@ -195,8 +312,13 @@ impl World {
             .map(|mut it| {
                 if it.0 == 0 {
                     let air = self.chunks[0].clone();
-                    it = ChunkIndex(self.chunks.len().try_into().unwrap());
-                    self.chunks.push(air);
+                    it = if let Some(index) = self.free_chunk_indices.pop() {
+                        self.chunks[index.0 as usize] = air;
+                        index
+                    } else {
+                        self.chunks.push(air);
+                        ChunkIndex((self.chunks.len() - 1).try_into().unwrap())
+                    };
                     self.position_to_index.insert(position, it);
                 }
                 &mut self.chunks[it.0 as usize]
@@ -223,8 +345,76 @@ impl World {
         self.request_mesh_update(position.plus(IVec3::NEG_Z));
     }
 
+    fn request_neighbour_light_updates(&mut self, position: ChunkPosition) {
+        for direction in DIRECTIONS {
+            self.request_light_update(position.plus(direction));
+        }
+    }
+
+    fn request_light_update(&mut self, position: ChunkPosition) {
+        // the shared chunk of air has full sky light, air in the shadow needs its own light
+        let clone_air = self.position_to_index.get(&position) == Some(&ChunkIndex(0))
+            && !light::open_sky_below(self.get_chunk(position.plus(IVec3::Y)));
+        if let Some(chunk) = self.get_chunk_mut(position, clone_air)
+            && !chunk.in_light_queue
+        {
+            chunk.in_light_queue = true;
+            self.light_queue.push_back(position);
+        }
+    }
+
+    /// Recomputes the light of changed chunks. If the light at a border changes, the neighbour
+    /// is updated as well, until the light is stable. Chunks that only contain air share full sky
+    /// light, unless the chunk above doesn't let it through everywhere. Then they get their own
+    /// light, so that caves below the surface stay dark.
+    pub fn update_light(&mut self) {
+        while let Some(position) = self.light_queue.pop_front() {
+            let Some(chunk) = self.get_chunk(position) else {
+                continue; // not loaded anymore
+            };
+            let neighbours = DIRECTIONS.map(|it| self.get_chunk(position.plus(it)));
+            let light = light::compute(chunk, &neighbours);
+
+            let changed = DIRECTIONS.map(|it| light::border_changed(chunk, &light, it));
+            let chunk = self.get_chunk_mut(position, false).unwrap();
+            chunk.in_light_queue = false;
+            if chunk.light == light {
+                continue;
+            }
+            chunk.light = light;
+
+            self.request_mesh_update(position);
+            for (direction, changed) in DIRECTIONS.into_iter().zip(changed) {
+                if changed {
+                    self.request_light_update(position.plus(direction));
+                    self.request_mesh_update(position.plus(direction));
+                }
+            }
+        }
+    }
+
+    /// Changes how meshes are generated and regenerates all of them.
+    pub fn set_meshing(&mut self, meshing: Meshing) {
+        if self.meshing == meshing {
+            return;
+        }
+        self.meshing = meshing;
+        let positions = self
+            .position_to_index
+            .iter()
+            .filter(|(_, index)| index.0 != 0)
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>();
+        for position in positions {
+            self.request_mesh_update(position);
+        }
+    }
+
     fn request_mesh_update(&mut self, position: ChunkPosition) {
+        let version = self.next_mesh_version;
+        self.next_mesh_version = self.next_mesh_version.wrapping_add(1);
         if let Some(chunk) = self.get_chunk_mut(position, false) {
+            chunk.mesh_version = version;
             if !chunk.in_mesh_queue {
                 chunk.in_mesh_queue = true;
                 self.mesh_queue.push_back(position);
@@ -232,59 +422,83 @@ impl World {
         }
     }
 
+    /// Walks through every block that the ray touches (Amanatides & Woo) and returns the first
+    /// one that isn't transparent. Blocks in chunks that aren't loaded are skipped.
     pub fn find_nearest_block_on_ray(
         &self,
         start_chunk: ChunkPosition,
         offset: Vec3,
         direction: Vec3,
-        max_distance: usize,
-    ) -> (Option<BlockPosition>, Option<BlockPosition>) {
-        let direction = direction.normalize();
-
-        // TODO properly intersect with the faces of neighbouring cubes and handle edges/corners
-        let divisor = 100;
-        let mut previous = None;
-
-        for i in 0..max_distance * divisor {
-            let distance = offset + i as f32 * direction * (1.0 / divisor as f32);
-
-            let position = start_chunk.block().plus(distance.floor().as_ivec3());
-            if Some(position) == previous {
-                continue;
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        let direction = direction.try_normalize()?;
+        let origin = start_chunk.block();
+
+        let mut cell = offset.floor().as_ivec3();
+        let mut step = IVec3::ZERO;
+        let mut t_max = Vec3::INFINITY;
+        let mut t_delta = Vec3::INFINITY;
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                t_max[axis] = (cell[axis] as f32 + 1.0 - offset[axis]) / direction[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                t_max[axis] = (cell[axis] as f32 - offset[axis]) / direction[axis];
+            }
+            if step[axis] != 0 {
+                t_delta[axis] = 1.0 / direction[axis].abs();
             }
+        }
 
-            if let Some(chunk) = self.get_chunk(position.chunk()) {
-                let relative = position.index() - position.chunk().block().index();
-                if !chunk.blocks[relative.x as usize][relative.y as usize][relative.z as usize]
-                    .transparent()
-                {
-                    return (previous, Some(position));
-                }
+        let mut normal = IVec3::ZERO;
+        let mut distance = 0.0;
+        loop {
+            let block = origin.plus(cell);
+            if self.get_block(block).is_some_and(|it| !it.transparent()) {
+                return Some(RayHit {
+                    block,
+                    normal,
+                    point: offset + direction * distance,
+                    distance,
+                });
             }
 
-            previous = Some(position);
+            let axis = t_max.min_position();
+            distance = t_max[axis];
+            if distance > max_distance {
+                return None;
+            }
+            cell[axis] += step[axis];
+            normal = IVec3::ZERO;
+            normal[axis] = -step[axis];
+            t_max[axis] += t_delta[axis];
         }
-        (None, None)
+    }
+
+    pub fn get_block(&self, position: BlockPosition) -> Option<Block> {
+        let chunk = self.get_chunk(position.chunk())?;
+        let relative = position.index() - position.chunk().block().index();
+        Some(chunk.get(
+            relative.x as usize,
+            relative.y as usize,
+            relative.z as usize,
+        ))
     }
 
     pub fn set_block(&mut self, position: BlockPosition, block: Block) -> Option<Block> {
-        if let Some(chunk) = self.get_chunk_mut(position.chunk(), !matches!(block, Block::Air)) {
+        if let Some(chunk) = self.get_chunk_mut(position.chunk(), block != Block::AIR) {
             let relative = position.index() - position.chunk().block().index();
 
-            let previous = mem::replace(
-                &mut chunk.blocks[relative.x as usize][relative.y as usize][relative.z as usize],
+            let previous = chunk.set(
+                relative.x as usize,
+                relative.y as usize,
+                relative.z as usize,
                 block,
             );
 
             if previous != block {
-                if let Block::Air = previous {
-                    assert!(chunk.non_air_block_count < Chunk::MAX_BLOCK_COUNT);
-                    chunk.non_air_block_count += 1;
-                }
-                if let Block::Air = block {
-                    assert!(chunk.non_air_block_count > 0);
-                    chunk.non_air_block_count -= 1;
-                }
+                chunk.modified = true;
                 let last = Chunk::SIZE as i32 - 1;
                 if relative.x == 0
                     || relative.y == 0
@@ -296,66 +510,205 @@ impl World {
                     chunk.compute_transparency();
                 }
 
-                self.request_mesh_update(position.plus(IVec3::X).chunk());
-                self.request_mesh_update(position.plus(IVec3::NEG_X).chunk());
-                self.request_mesh_update(position.plus(IVec3::Y).chunk());
-                self.request_mesh_update(position.plus(IVec3::NEG_Y).chunk());
-                self.request_mesh_update(position.plus(IVec3::Z).chunk());
-                self.request_mesh_update(position.plus(IVec3::NEG_Z).chunk());
+                self.history.record(Edit {
+                    position,
+                    previous,
+                    new: block,
+                });
+                self.request_light_update(position.chunk());
+                self.neighbour_updates.push(position);
+                for direction in DIRECTIONS {
+                    self.request_mesh_update(position.plus(direction).chunk());
+                    self.neighbour_updates.push(position.plus(direction));
+                }
             }
             return Some(previous);
         }
-        if !matches!(block, Block::Air) && self.get_chunk(position.chunk()).is_some() {
-            Some(Block::Air)
+        if block != Block::AIR && self.get_chunk(position.chunk()).is_some() {
+            Some(Block::AIR)
         } else {
             None
         }
     }
 
-    pub fn collide(&self, chunk: ChunkPosition, offset: Vec3) -> bool {
-        if let Some(chunk) = self.get_chunk(chunk) {
-            let p = offset.as_uvec3();
-            let block = chunk.blocks[p.x as usize][p.y as usize][p.z as usize];
-            !block.transparent()
-        } else {
-            true
+    /// Groups the block changes until [`World::end_edit`] into one batch that can be undone.
+    pub fn begin_edit(&mut self) {
+        self.history.begin();
+    }
+
+    pub fn end_edit(&mut self) {
+        self.history.end();
+    }
+
+    /// Reverts the last batch of edits. Fails without changing anything if there is nothing to
+    /// undo or if a block of the batch is in a chunk that isn't loaded, so that the batch isn't
+    /// split.
+    pub fn undo(&mut self) -> Result<(), String> {
+        let batch = self.history.next_undo().ok_or("nothing to undo")?;
+        self.check_loaded(batch)?;
+        let edits = self.history.undo().unwrap();
+        self.replay(edits.into_iter().map(|it| (it.position, it.previous)));
+        Ok(())
+    }
+
+    /// Applies the last undone batch of edits again, see [`World::undo`].
+    pub fn redo(&mut self) -> Result<(), String> {
+        let batch = self.history.next_redo().ok_or("nothing to redo")?;
+        self.check_loaded(batch)?;
+        let edits = self.history.redo().unwrap();
+        self.replay(edits.into_iter().map(|it| (it.position, it.new)));
+        Ok(())
+    }
+
+    fn check_loaded(&self, batch: &[Edit]) -> Result<(), String> {
+        let unloaded = (batch.iter())
+            .filter(|it| self.get_chunk(it.position.chunk()).is_none())
+            .count();
+        if unloaded > 0 {
+            return Err(format!(
+                "{unloaded} blocks are in chunks that aren't loaded"
+            ));
         }
+        Ok(())
     }
 
-    pub fn generate_around(&mut self, chunk: ChunkPosition) {
-        let v = self.view_distance as i32;
+    fn replay(&mut self, blocks: impl Iterator<Item = (BlockPosition, Block)>) {
+        debug_assert!(!self.history.recording());
+        for (position, block) in blocks {
+            self.set_block(position, block);
+        }
+    }
 
-        while chunk.index().x - v < self.min.0 {
-            self.min.0 -= 1;
-            for z in self.min.1..=self.max.1 {
-                self.generation_queue
-                    .push_back((self.min.0, self.highest_generated_chunk, z));
+    /// Lets blocks react to changes of themselves or their neighbours, until nothing changes
+    /// anymore. Falling blocks fall immediately, fluids are updated after [`fluid::DELAY`].
+    pub fn process_neighbour_updates(&mut self) {
+        while !self.neighbour_updates.is_empty() {
+            for position in mem::take(&mut self.neighbour_updates) {
+                self.scheduled_ticks.schedule(position, fluid::DELAY);
+                gravity::update(self, position);
             }
         }
-        while chunk.index().x + v > self.max.0 {
-            self.max.0 += 1;
-            for z in self.min.1..=self.max.1 {
-                self.generation_queue
-                    .push_back((self.max.0, self.highest_generated_chunk, z));
+    }
+
+    /// Advances the game by one tick: runs the scheduled updates that are due and random ticks
+    /// of a few blocks in every loaded chunk.
+    pub fn tick(&mut self, random: &mut StdRng) {
+        let scheduled = self.scheduled_ticks.advance();
+        fluid::update(self, scheduled.into_iter().collect::<HashSet<_>>());
+
+        let mut random_ticks = vec![];
+        for (position, index) in self.position_to_index.iter() {
+            if index.0 == 0 {
+                continue; // air
             }
-        }
-        while chunk.index().z - v < self.min.1 {
-            self.min.1 -= 1;
-            for x in self.min.0..=self.max.0 {
-                self.generation_queue
-                    .push_back((x, self.highest_generated_chunk, self.min.1));
+            let chunk = &self.chunks[index.0 as usize];
+            for _ in 0..RANDOM_TICKS_PER_CHUNK {
+                let p =
+                    IVec3::from_array([(); 3].map(|_| random.random_range(0..Chunk::SIZE as i32)));
+                let block = chunk.get(p.x as usize, p.y as usize, p.z as usize);
+                if block.properties().random_ticks {
+                    random_ticks.push(position.block().plus(p));
+                }
             }
         }
-        while chunk.index().z + v > self.max.1 {
-            self.max.1 += 1;
-            for x in self.min.0..=self.max.0 {
-                self.generation_queue
-                    .push_back((x, self.highest_generated_chunk, self.max.1));
+        for position in random_ticks {
+            grass::random_tick(self, position, random);
+        }
+
+        self.process_neighbour_updates();
+    }
+
+    pub fn generate_around(&mut self, chunk: ChunkPosition) {
+        if chunk != self.center {
+            self.center = chunk;
+            self.queue_missing_chunks();
+        }
+    }
+
+    /// Chunks that are out of view afterwards are only unloaded by [`World::crop`].
+    pub fn set_view_distance(&mut self, view_distance: u16, vertical_view_distance: u16) {
+        self.view_distance = view_distance_vector(view_distance, vertical_view_distance);
+        self.queue_missing_chunks();
+    }
+
+    /// Replaces the generation queue with the chunks in view that are neither loaded nor
+    /// generating.
+    fn queue_missing_chunks(&mut self) {
+        let center = self.center.index();
+        let d = self.view_distance;
+        self.generation_queue.clear();
+        for x in -d.x..=d.x {
+            for y in -d.y..=d.y {
+                for z in -d.z..=d.z {
+                    let position = ChunkPosition::from_chunk_index(center + IVec3::new(x, y, z));
+                    if in_view(self.center, d, position)
+                        && !self.position_to_index.contains_key(&position)
+                        && !self.generating.contains(&position)
+                    {
+                        self.generation_queue.push(position);
+                    }
+                }
             }
         }
+        self.generation_queue_sorted = false;
+    }
+}
+
+fn view_distance_vector(view_distance: u16, vertical_view_distance: u16) -> IVec3 {
+    let (horizontal, vertical) = (view_distance as i32, vertical_view_distance as i32);
+    IVec3::new(horizontal, vertical, horizontal)
+}
+
+fn in_view(center: ChunkPosition, view_distance: IVec3, position: ChunkPosition) -> bool {
+    let offset = position.index() - center.index();
+    offset.x * offset.x + offset.z * offset.z <= view_distance.x * view_distance.x
+        && offset.y.abs() <= view_distance.y
+}
+
+#[derive(Clone, Copy)]
+struct GenerationFocus {
+    chunk: ChunkPosition,
+    /// Normalized or zero
+    view: Vec3,
+    /// Normalized or zero
+    travel: Vec3,
+}
+
+impl GenerationFocus {
+    /// Lower is more important. Chunks are generated by distance, but chunks in the direction of
+    /// view and travel count as up to half as far away.
+    fn priority(&self, position: ChunkPosition) -> f32 {
+        let offset = (position.index() - self.chunk.index()).as_vec3();
+        let direction = offset.normalize_or_zero();
+        let view = direction.dot(self.view).max(0.0);
+        let travel = direction.dot(self.travel).max(0.0);
+        offset.length() * (1.0 - 0.25 * view - 0.25 * travel)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub block: BlockPosition,
+    /// Normal of the face through which the ray entered the block. Zero if the ray started inside.
+    pub normal: IVec3,
+    /// Relative to the start chunk, like the offset of the ray
+    pub point: Vec3,
+    pub distance: f32,
+}
+
+/// Blocks of every loaded chunk that get a random tick in each game tick
+const RANDOM_TICKS_PER_CHUNK: usize = 3;
+
+pub const DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[derive(Clone, Copy)]
 pub struct ChunkNeighbours<'a> {
     pub pos_x: &'a Chunk,
     pub neg_x: &'a Chunk,
@@ -367,3 +720,177 @@ pub struct ChunkNeighbours<'a> {
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 struct ChunkIndex(u32);
+
+#[cfg(test)]
+impl World {
+    /// A world with an empty chunk at the origin, the chunks around it aren't loaded.
+    pub fn test_world(storage: Option<RegionStorage>) -> Self {
+        let origin = ChunkPosition::from_chunk_index(IVec3::ZERO);
+        let mut world = World::new(1, 1, origin, storage);
+        world.add_chunk(origin, Chunk::default());
+        world
+    }
+
+    /// Sets the blocks in the box between the corners, both are included.
+    pub fn fill(&mut self, a: IVec3, b: IVec3, block: Block) {
+        let (min, max) = (a.min(b), a.max(b));
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    self.set_block(at(x, y, z), block);
+                }
+            }
+        }
+    }
+
+    /// Fills the bottom layer of the chunk at the origin.
+    pub fn fill_floor(&mut self, block: Block) {
+        let last = Chunk::SIZE as i32 - 1;
+        self.fill(IVec3::ZERO, IVec3::new(last, 0, last), block);
+    }
+}
+
+#[cfg(test)]
+impl ChunkNeighbours<'_> {
+    /// The same chunk on every side, e.g. air
+    pub fn all(chunk: &Chunk) -> ChunkNeighbours<'_> {
+        ChunkNeighbours {
+            pos_x: chunk,
+            neg_x: chunk,
+            pos_y: chunk,
+            neg_y: chunk,
+            pos_z: chunk,
+            neg_z: chunk,
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn at(x: i32, y: i32, z: i32) -> BlockPosition {
+    BlockPosition::new(IVec3::new(x, y, z))
+}
+
+#[cfg(test)]
+#[test]
+fn test_generation_range() {
+    let origin = ChunkPosition::from_chunk_index(IVec3::ZERO);
+    let mut world = World::new(2, 1, origin, None);
+    // 13 columns within a radius of 2
+    assert_eq!(world.generation_queue.len(), 13 * 3);
+    assert!(
+        !world
+            .generation_queue
+            .contains(&origin.plus(IVec3::new(2, 0, 1)))
+    );
+    assert_eq!(world.next_chunk_to_generate(), Some(origin));
+    assert_eq!(world.generating_count(), 1);
+    for position in mem::take(&mut world.generation_queue) {
+        world.add_air_chunk(position);
+    }
+
+    // moving up only requests the new layer above
+    let above = origin.plus(IVec3::Y);
+    world.generate_around(above);
+    assert_eq!(world.generation_queue.len(), 13);
+    assert!(world.generation_queue.iter().all(|it| it.index().y == 2));
+
+    world.crop(above);
+    assert!(world.get_chunk(origin.plus(IVec3::NEG_Y)).is_none());
+    assert!(world.get_chunk(above).is_some());
+    // the chunk below left the range while it was generating
+    assert!(!world.finish_generation(origin.plus(IVec3::NEG_Y)));
+    assert!(world.finish_generation(origin));
+    assert_eq!(world.generating_count(), 0);
+
+    // growing requests the new ring, shrinking unloads the chunks outside
+    world.set_view_distance(3, 1);
+    assert!(world.generation_queue.contains(&above.plus(IVec3::X * 3)));
+    assert!(!world.generation_queue.contains(&above.plus(IVec3::X * 2)));
+    world.set_view_distance(1, 1);
+    world.crop(above);
+    assert!(world.get_chunk(above.plus(IVec3::X * 2)).is_none());
+    assert!(world.get_chunk(above.plus(IVec3::X)).is_some());
+    assert!(world.generation_queue.iter().all(|it| world.in_range(*it)));
+}
+
+#[cfg(test)]
+#[test]
+fn test_generation_order() {
+    let origin = ChunkPosition::from_chunk_index(IVec3::ZERO);
+    let mut world = World::new(4, 1, origin, None);
+    world.set_generation_focus(origin, Vec3::X, Vec3::ZERO);
+    let order = std::iter::from_fn(|| world.next_chunk_to_generate()).collect::<Vec<_>>();
+    let at = |x| order.iter().position(|it| *it == origin.plus(IVec3::X * x));
+    assert_eq!(order[0], origin);
+    assert!(at(2) < at(-2));
+
+    // turning around
+    let mut world = World::new(4, 1, origin, None);
+    world.set_generation_focus(origin, Vec3::NEG_X, Vec3::NEG_X);
+    let order = std::iter::from_fn(|| world.next_chunk_to_generate()).collect::<Vec<_>>();
+    let at = |x| order.iter().position(|it| *it == origin.plus(IVec3::X * x));
+    assert!(at(-3) < at(2));
+
+    // leaving chunks behind cancels their generation
+    world.generation_queue.push(origin.plus(IVec3::X * 4));
+    world.generate_around(origin.plus(IVec3::NEG_X * 4));
+    world.crop(origin.plus(IVec3::NEG_X * 4));
+    assert!(world.generation_queue.iter().all(|it| it.index().x <= 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_ray() {
+    let origin = ChunkPosition::from_chunk_index(IVec3::ZERO);
+    let mut world = World::new(1, 1, origin, None);
+    let mut chunk = Chunk::default();
+    let stone = Block::named("stone");
+    chunk.set(5, 0, 0, stone);
+    chunk.set(1, 0, 0, stone);
+    chunk.set(0, 1, 0, stone);
+    world.add_chunk(origin, chunk);
+
+    let hit = world
+        .find_nearest_block_on_ray(origin, Vec3::new(3.5, 0.5, 0.5), Vec3::X, 10.0)
+        .unwrap();
+    assert_eq!(hit.block, BlockPosition::new(IVec3::new(5, 0, 0)));
+    assert_eq!(hit.normal, IVec3::NEG_X);
+    assert_eq!(hit.distance, 1.5);
+    assert_eq!(hit.point, Vec3::new(5.0, 0.5, 0.5));
+
+    let miss = world.find_nearest_block_on_ray(origin, Vec3::new(3.5, 0.5, 0.5), Vec3::X, 1.0);
+    assert_eq!(miss, None);
+
+    // doesn't slip through the gap between two diagonal blocks
+    let hit = world
+        .find_nearest_block_on_ray(
+            origin,
+            Vec3::new(0.5, 0.5, 0.5),
+            Vec3::new(1.0, 1.0, 0.0),
+            10.0,
+        )
+        .unwrap();
+    assert!(hit.distance < 1.0);
+    assert_ne!(hit.normal, IVec3::ZERO);
+}
+
+#[cfg(test)]
+#[test]
+fn test_light_below_ground() {
+    let mut world = World::test_world(None);
+    let last = Chunk::SIZE as i32 - 1;
+    world.fill(IVec3::ZERO, IVec3::splat(last), Block::named("stone"));
+    // a cave below the ground, next to open sky
+    let cave = ChunkPosition::from_chunk_index(IVec3::NEG_Y);
+    world.add_air_chunk(cave);
+    world.add_air_chunk(cave.plus(IVec3::X));
+    world.update_light();
+
+    let cave_chunk = world.get_chunk(cave).unwrap();
+    assert_eq!(light::sky(cave_chunk.light(15, 8, 8)), light::MAX - 1);
+    assert_eq!(light::sky(cave_chunk.light(8, 8, 8)), light::MAX - 8);
+    assert_eq!(cave_chunk.light(1, 8, 8), 0);
+    let open = world.get_chunk(cave.plus(IVec3::X)).unwrap();
+    assert_eq!(open.light(8, 8, 8), light::FULL_SKY);
+    assert!(open.light.is_empty());
+}
@@ -15,6 +15,10 @@ pub struct Statistics {
     pub replaced_meshes: usize,
     pub recycled_index_buffers: usize,
     pub recycled_vertex_buffers: usize,
+    /// Chunks waiting for a generator worker, as last reported by the simulation
+    pub queued_chunks: usize,
+    /// Chunks on the generator workers, as last reported by the simulation
+    pub generating_chunks: usize,
 }
 
 pub struct FrameInfo {
@@ -33,6 +37,8 @@ pub struct ChunkInfo {
 pub struct ChunkMeshInfo {
     pub time: Duration,
     pub face_count: usize,
+    /// Size of the vertex and index data in bytes
+    pub size: usize,
     pub recycled_index_buffer: bool,
     pub recycled_vertex_buffer: bool,
 }
@@ -50,6 +56,8 @@ impl Statistics {
             replaced_meshes: 0,
             recycled_index_buffers: 0,
             recycled_vertex_buffers: 0,
+            queued_chunks: 0,
+            generating_chunks: 0,
         }
     }
 
@@ -69,6 +77,27 @@ impl Statistics {
         self.frame_infos.push(info);
     }
 
+    /// Chunks generated per second during about the last second
+    fn chunk_throughput(&self) -> f64 {
+        let mut duration = Duration::ZERO;
+        let mut first = None;
+        for (i, frame) in self.frame_infos.iter().enumerate().rev() {
+            if duration >= Duration::from_secs(1) {
+                break;
+            }
+            duration += frame.frame_time;
+            first = Some(i);
+        }
+        let Some(first) = first else {
+            return 0.0;
+        };
+        let before = first
+            .checked_sub(1)
+            .map_or(0, |it| self.frame_infos[it].chunk_info_count);
+        let generated = self.frame_infos.last().unwrap().chunk_info_count - before;
+        generated as f64 / duration.as_secs_f64()
+    }
+
     pub fn print_last_frame(&self, w: &mut dyn io::Write) -> io::Result<()> {
         let start = Timer::now();
         let frame = self.frame_infos.last().unwrap();
@@ -133,6 +162,13 @@ impl Statistics {
                     1000.0 * chunk_infos_duration / chunk_infos.len() as f64,
                 )?;
             }
+            writeln!(
+                w,
+                "    queue: {} waiting, {} generating, {:.1} chunks/s",
+                self.queued_chunks,
+                self.generating_chunks,
+                self.chunk_throughput(),
+            )?;
         }
 
         writeln!(w, "Chunk meshes:")?;
@@ -161,6 +197,14 @@ impl Statistics {
                     1000.0 * chunk_mesh_infos_duration / chunk_mesh_infos.len() as f64,
                 )?;
             }
+            let faces: usize = self.chunk_mesh_infos.iter().map(|it| it.face_count).sum();
+            let size: usize = self.chunk_mesh_infos.iter().map(|it| it.size).sum();
+            writeln!(
+                w,
+                "    size: {:.1} faces, {:.3}kB average",
+                faces as f64 / self.chunk_mesh_infos.len() as f64,
+                size as f64 / 1000.0 / self.chunk_mesh_infos.len() as f64,
+            )?;
             writeln!(
                 w,
                 "    invisible: {} air, {} underground",
@@ -185,3 +229,21 @@ impl Statistics {
         )
     }
 }
+
+#[cfg(test)]
+#[test]
+fn test_chunk_throughput() {
+    let mut statistics = Statistics::new();
+    assert_eq!(statistics.chunk_throughput(), 0.0);
+    for i in 0..30 {
+        statistics.end_frame(FrameInfo {
+            player_position: Vec3::ZERO,
+            player_orientation: Vec3::Z,
+            frame_time: Duration::from_millis(100),
+            chunk_info_count: i * 4,
+            chunk_mesh_info_count: 0,
+        });
+    }
+    // 10 frames with 4 chunks each
+    assert!((statistics.chunk_throughput() - 40.0).abs() < 1e-6);
+}
@@ -68,14 +68,30 @@ impl WorkerMessage {
 pub enum MessageTag {
     InitSimulation,
     InitGenerator,
-    GenerateColumn,
-    GenerateColumnReply,
+    GenerateChunk,
+    GenerateChunkReply,
+    MeshChunk,
     MeshData,
     ChunkInfo,
     PlayerCommand,
     MovementCommand,
     MovementCommandReply,
     ChunkRemoval,
+    SetMeshing,
+    SetViewDistance,
+    Undo,
+    Redo,
+    SelectCorner,
+    EditSelection,
+    CopySelection,
+    TransformClipboard,
+    PasteClipboard,
+    SaveClipboard,
+    LoadClipboard,
+    ImportVox,
+    ExportVox,
+    Shutdown,
+    ShutdownComplete,
 }
 
 pub fn update(